@@ -0,0 +1,158 @@
+use std::collections::BTreeMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::ecs::Owner;
+use crate::events::*;
+use crate::game::*;
+use crate::manifest::*;
+use crate::score::*;
+
+pub const ACHIEVEMENTS_PATH: &str = "achievements.json";
+
+/// What the player has unlocked and done over every run, persisted between runs.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Progress {
+    /// Seconds since the Unix epoch each achievement was unlocked at, by id.
+    pub unlocked: BTreeMap<String, u64>,
+    pub counters: BTreeMap<Counter, u32>,
+}
+
+/// Checks the achievements from the manifest against gameplay events.
+#[derive(Default)]
+pub struct Achievements {
+    progress: Progress,
+    /// Counts for the current run.
+    counters: BTreeMap<Counter, u32>,
+    time: f32,
+    reversal_start: Option<f32>,
+    fastest_reversal: Option<f32>,
+    lowest_health: f32,
+}
+
+impl Achievements {
+    /// Missing or unreadable progress starts from nothing unlocked.
+    pub fn load() -> Self {
+        let progress = std::fs::read(ACHIEVEMENTS_PATH)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        let mut achievements = Self {
+            progress,
+            ..Default::default()
+        };
+        achievements.start_run();
+        achievements
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(&self.progress).map_err(|err| err.to_string())?;
+        std::fs::write(ACHIEVEMENTS_PATH, json).map_err(|err| err.to_string())
+    }
+
+    pub fn start_run(&mut self) {
+        self.counters.clear();
+        self.time = 0.0;
+        self.reversal_start = None;
+        self.fastest_reversal = None;
+        self.lowest_health = 1.0;
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        let counters: &[Counter] = match event {
+            GameEvent::BulletFired {
+                owner: Owner::Player,
+                ..
+            } => &[Counter::Shots],
+            GameEvent::Scored { kind, .. } => match kind {
+                ScoreKind::Interception => &[Counter::Interceptions, Counter::Annihilations],
+                ScoreKind::Annihilation => &[Counter::Annihilations],
+                ScoreKind::TowerShot => &[Counter::TowerShots],
+                _ => &[],
+            },
+            GameEvent::RolesReversed { .. } => {
+                self.reversal_start = Some(self.time);
+                &[Counter::Reversals]
+            }
+            GameEvent::RolesRestored => {
+                if let Some(start) = self.reversal_start.take() {
+                    let time = self.time - start;
+                    self.fastest_reversal = Some(
+                        self.fastest_reversal
+                            .map_or(time, |fastest| fastest.min(time)),
+                    );
+                }
+                &[Counter::ReversalsWon]
+            }
+            GameEvent::PlayerJumped { .. } => &[Counter::Jumps],
+            GameEvent::PlayerKilled { .. } => &[Counter::Deaths],
+            GameEvent::CheckpointReached { .. } => &[Counter::Checkpoints],
+            _ => &[],
+        };
+        for &counter in counters {
+            *self.counters.entry(counter).or_default() += 1;
+            *self.progress.counters.entry(counter).or_default() += 1;
+        }
+    }
+
+    /// Checks every achievement against the run so far and returns the ones it unlocked. Only
+    /// lifetime goals count once the bot has played part of the run.
+    pub fn update<'a>(
+        &mut self,
+        infos: &'a [AchievementInfo],
+        game: &Game,
+    ) -> Vec<&'a AchievementInfo> {
+        self.time = game.play_time();
+        self.lowest_health = self.lowest_health.min(game.scene.tower().health());
+
+        let unlocked = infos
+            .iter()
+            .filter(|info| !self.progress.unlocked.contains_key(&info.id))
+            .filter(|info| self.reached(info.goal, game))
+            .collect::<Vec<_>>();
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        for info in &unlocked {
+            self.progress.unlocked.insert(info.id.clone(), now);
+        }
+        unlocked
+    }
+
+    fn reached(&self, goal: Goal, game: &Game) -> bool {
+        let lifetime = matches!(goal, Goal::Count { lifetime: true, .. });
+        if game.stats.autoplayed && !lifetime {
+            return false;
+        }
+        match goal {
+            Goal::Count {
+                counter,
+                count,
+                lifetime,
+            } => {
+                let counters = if lifetime {
+                    &self.progress.counters
+                } else {
+                    &self.counters
+                };
+                counters.get(&counter).copied().unwrap_or_default() >= count
+            }
+            Goal::QuickReversal { seconds } => {
+                self.fastest_reversal.is_some_and(|time| time < seconds)
+            }
+            Goal::Survive {
+                seconds,
+                min_health,
+            } => self.time >= seconds && self.lowest_health >= min_health,
+            Goal::Score { points } => game.score.total() >= points,
+            Goal::Combo { count } => game.score.best_combo() >= count,
+        }
+    }
+
+    pub fn unlocked(&self, id: &str) -> bool {
+        self.progress.unlocked.contains_key(id)
+    }
+}
@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::manifest::*;
+
+pub const ARCHIVE_PATH: &str = "Assets.pak";
+pub const ASSETS_DIR: &str = "Assets";
+
+const MAGIC: &[u8; 8] = b"PRSNPAK\0";
+const FORMAT_VERSION: u32 = 1;
+
+/// Every file listed in the manifest packed into a single file, checksummed per entry.
+///
+/// Layout (little endian): magic, format version, manifest version, entry count, then one
+/// `name length (u16), name, size (u32), crc32 (u32)` header per entry followed by the data
+/// of all entries in the same order.
+pub struct Archive {
+    manifest_version: u32,
+    entries: HashMap<String, Vec<u8>>,
+}
+
+impl Archive {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let bytes = std::fs::read(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+        Self::parse(&bytes).map_err(|err| format!("{}: {}", path.display(), err))
+    }
+
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, offset: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err("Not an asset archive".to_owned());
+        }
+        let format_version = reader.u32()?;
+        if format_version != FORMAT_VERSION {
+            return Err(format!(
+                "Unsupported archive version {} (expected {})",
+                format_version, FORMAT_VERSION
+            ));
+        }
+        let manifest_version = reader.u32()?;
+        if manifest_version != MANIFEST_VERSION {
+            return Err(format!(
+                "Unsupported manifest version {} (expected {})",
+                manifest_version, MANIFEST_VERSION
+            ));
+        }
+
+        let count = reader.u32()?;
+        let mut headers = Vec::new();
+        for _ in 0..count {
+            let length = reader.u16()? as usize;
+            let name = String::from_utf8(reader.take(length)?.to_vec())
+                .map_err(|_| "Entry name is not valid UTF-8".to_owned())?;
+            headers.push((name, reader.u32()? as usize, reader.u32()?));
+        }
+
+        let mut entries = HashMap::new();
+        for (name, size, checksum) in headers {
+            let data = reader.take(size)?;
+            if crc32(data) != checksum {
+                return Err(format!("Checksum mismatch for {}", name));
+            }
+            entries.insert(name, data.to_vec());
+        }
+
+        Ok(Self {
+            manifest_version,
+            entries,
+        })
+    }
+
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        self.entries
+            .get(name)
+            .cloned()
+            .ok_or_else(|| format!("{} is not in the archive", name))
+    }
+
+    pub fn manifest_version(&self) -> u32 {
+        self.manifest_version
+    }
+}
+
+/// Packs the manifest in `dir` and every file it references into `output`.
+pub fn pack(dir: impl AsRef<Path>, output: impl AsRef<Path>) -> Result<usize, String> {
+    let dir = dir.as_ref();
+    let read = |name: &str| {
+        let path = dir.join(name);
+        std::fs::read(&path).map_err(|err| format!("{}: {}", path.display(), err))
+    };
+
+    let manifest_bytes = read(MANIFEST_PATH)?;
+    let manifest = Manifest::parse(&manifest_bytes)?;
+
+    let mut entries = vec![(MANIFEST_PATH.to_owned(), manifest_bytes)];
+    for file in manifest.files() {
+        let data = read(&file)?;
+        entries.push((file, data));
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    bytes.extend_from_slice(&manifest.version.to_le_bytes());
+    bytes.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+    for (name, data) in &entries {
+        bytes.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&crc32(data).to_le_bytes());
+    }
+    for (_, data) in &entries {
+        bytes.extend_from_slice(data);
+    }
+
+    let output = output.as_ref();
+    std::fs::write(output, bytes).map_err(|err| format!("{}: {}", output.display(), err))?;
+    Ok(entries.len())
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, count: usize) -> Result<&'a [u8], String> {
+        let end = self.offset + count;
+        let slice = self
+            .bytes
+            .get(self.offset..end)
+            .ok_or_else(|| "Archive is truncated".to_owned())?;
+        self.offset = end;
+        Ok(slice)
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Packs the game's own assets and returns the bytes of the archive.
+    fn packed() -> Vec<u8> {
+        let output = std::env::temp_dir().join(format!("personality-{}.pak", std::process::id()));
+        pack(ASSETS_DIR, &output).unwrap();
+        let bytes = std::fs::read(&output).unwrap();
+        std::fs::remove_file(&output).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+        assert_eq!(crc32(b""), 0);
+    }
+
+    #[test]
+    fn pack_and_parse_round_trip() {
+        let archive = Archive::parse(&packed()).unwrap();
+        assert_eq!(archive.manifest_version(), MANIFEST_VERSION);
+
+        let manifest_bytes = std::fs::read(Path::new(ASSETS_DIR).join(MANIFEST_PATH)).unwrap();
+        assert_eq!(archive.read(MANIFEST_PATH).unwrap(), manifest_bytes);
+        for file in Manifest::parse(&manifest_bytes).unwrap().files() {
+            let data = std::fs::read(Path::new(ASSETS_DIR).join(&file)).unwrap();
+            assert_eq!(archive.read(&file).unwrap(), data);
+        }
+        assert!(archive.read("missing.png").is_err());
+    }
+
+    #[test]
+    fn bad_checksum_is_rejected() {
+        let mut bytes = packed();
+        // The last byte belongs to the data of the last entry.
+        *bytes.last_mut().unwrap() ^= 0xFF;
+        let err = Archive::parse(&bytes).err().unwrap();
+        assert!(err.starts_with("Checksum mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn truncated_archive_is_rejected() {
+        let bytes = packed();
+        // Cut in the magic, in the header and in the last entry's data.
+        for length in [0, MAGIC.len() + 6, bytes.len() - 1] {
+            assert_eq!(
+                Archive::parse(&bytes[..length]).err().as_deref(),
+                Some("Archive is truncated")
+            );
+        }
+    }
+}
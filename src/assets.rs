@@ -0,0 +1,333 @@
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::fmt;
+use std::rc::Rc;
+
+use crate::archive::*;
+use crate::manifest::*;
+use crate::resources::*;
+
+/// Where asset files are read from: the packed archive in the web build, the asset directory
+/// otherwise.
+pub enum AssetSource {
+    Directory(std::path::PathBuf),
+    Archive(Archive),
+}
+
+impl AssetSource {
+    pub fn read(&self, name: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Self::Directory(dir) => std::fs::read(dir.join(name)).map_err(|err| err.to_string()),
+            Self::Archive(archive) => archive.read(name),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct AssetError {
+    pub path: String,
+    pub reason: String,
+}
+
+/// Every asset that failed to load, reported together instead of panicking on the first one.
+#[derive(Clone, Debug)]
+pub struct AssetErrors(pub Vec<AssetError>);
+
+impl fmt::Display for AssetErrors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} asset(s) failed to load, using placeholders:",
+            self.0.len()
+        )?;
+        for error in &self.0 {
+            writeln!(f, "  {}: {}", error.path, error.reason)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AssetErrors {}
+
+/// Name-keyed registry of everything loaded from `Assets/`. Each asset is loaded once and
+/// shared through [`Rc`] handles; anything missing or corrupt is replaced by a placeholder.
+pub struct Assets {
+    source: AssetSource,
+    manifest: Manifest,
+    textures: HashMap<String, Rc<Picture>>,
+    animations: HashMap<String, Rc<Animation>>,
+    images: HashMap<String, Rc<Image>>,
+    waves: HashMap<String, Rc<Wave>>,
+
+    placeholder_texture: Rc<Picture>,
+    placeholder_animation: Rc<Animation>,
+    placeholder_image: Rc<Image>,
+    placeholder_wave: Rc<Wave>,
+
+    errors: Vec<AssetError>,
+}
+
+/// The window textures are uploaded to, or `None` to only keep their sizes.
+type Window<'a> = Option<(&'a mut RaylibHandle, &'a RaylibThread)>;
+
+impl Assets {
+    pub fn load(rl: &mut RaylibHandle, thread: &RaylibThread) -> Self {
+        Self::load_into(Some((rl, thread)))
+    }
+
+    /// Loads without a window for headless runs. Nothing can be drawn, but every texture
+    /// still has its size.
+    pub fn load_headless() -> Self {
+        Self::load_into(None)
+    }
+
+    fn load_into(mut window: Window) -> Self {
+        let mut errors = Vec::new();
+        // Only the web build reads the pack, so one lying around can't shadow `Assets/`.
+        let archive = if cfg!(target_arch = "wasm32") {
+            check(&mut errors, ARCHIVE_PATH, Archive::open(ARCHIVE_PATH))
+        } else {
+            None
+        };
+        let source = archive.map_or_else(
+            || AssetSource::Directory(ASSETS_DIR.into()),
+            AssetSource::Archive,
+        );
+
+        let mut assets = Self {
+            source,
+            manifest: Manifest::default(),
+            textures: HashMap::new(),
+            animations: HashMap::new(),
+            images: HashMap::new(),
+            waves: HashMap::new(),
+
+            placeholder_texture: Rc::new(placeholder_texture(&mut window)),
+            placeholder_animation: Rc::new(Animation::still(placeholder_texture(&mut window))),
+            placeholder_image: Rc::new(Image::gen_image_color(1, 1, Color::BLANK)),
+            placeholder_wave: Rc::new(silence()),
+
+            errors,
+        };
+
+        let manifest = assets.read_manifest();
+        if let Some(manifest) = check(&mut assets.errors, MANIFEST_PATH, manifest) {
+            assets.manifest = manifest;
+            let errors = assets.load_entries(window, |_| true);
+            assets.errors.extend(errors);
+        }
+        assets
+    }
+
+    /// Reloads every asset built from one of the `changed` files. Assets that fail to reload
+    /// keep their previous version; the failures are returned.
+    pub fn reload(
+        &mut self,
+        rl: &mut RaylibHandle,
+        thread: &RaylibThread,
+        changed: &[String],
+    ) -> Vec<AssetError> {
+        if changed.iter().any(|path| path == MANIFEST_PATH) {
+            let mut errors = Vec::new();
+            if let Some(manifest) = check(&mut errors, MANIFEST_PATH, self.read_manifest()) {
+                self.manifest = manifest;
+                errors.extend(self.load_entries(Some((rl, thread)), |_| true));
+            }
+            return errors;
+        }
+        self.load_entries(Some((rl, thread)), |path| {
+            changed.iter().any(|file| file == path)
+        })
+    }
+
+    fn read_manifest(&self) -> Result<Manifest, String> {
+        self.source
+            .read(MANIFEST_PATH)
+            .and_then(|bytes| Manifest::parse(&bytes))
+    }
+
+    fn load_entries(
+        &mut self,
+        mut window: Window,
+        filter: impl Fn(&str) -> bool,
+    ) -> Vec<AssetError> {
+        let mut errors = Vec::new();
+        let source = &self.source;
+
+        for (name, path) in &self.manifest.textures {
+            if !filter(path) {
+                continue;
+            }
+            let texture = load_image(source, path).and_then(|image| picture(&mut window, &image));
+            if let Some(texture) = check(&mut errors, path, texture) {
+                self.textures.insert(name.clone(), Rc::new(texture));
+            }
+        }
+        for (name, info) in &self.manifest.animations {
+            if !filter(&info.sheet) && !filter(&info.atlas) {
+                continue;
+            }
+            let animation = load_image(source, &info.sheet)
+                .and_then(|image| picture(&mut window, &image))
+                .and_then(|texture| {
+                    let atlas = source.read(&info.atlas)?;
+                    Animation::from_atlas(texture, &atlas)
+                });
+            if let Some(animation) = check(&mut errors, &info.atlas, animation) {
+                self.animations.insert(name.clone(), Rc::new(animation));
+            }
+        }
+        for (name, path) in &self.manifest.images {
+            if !filter(path) {
+                continue;
+            }
+            if let Some(image) = check(&mut errors, path, load_image(source, path)) {
+                self.images.insert(name.clone(), Rc::new(image));
+            }
+        }
+        for (name, path) in &self.manifest.sounds {
+            if !filter(path) {
+                continue;
+            }
+            let wave = source
+                .read(path)
+                .and_then(|bytes| wave_from_memory(&extension(path), &bytes));
+            if let Some(wave) = check(&mut errors, path, wave) {
+                self.waves.insert(name.clone(), Rc::new(wave));
+            }
+        }
+
+        errors
+    }
+
+    /// A file system path to the music track `name`, unpacked to a temporary file when the
+    /// assets come from an archive, since music is streamed from disk.
+    pub fn music_path(&self, name: &str) -> Result<std::path::PathBuf, String> {
+        let path = self
+            .manifest
+            .music
+            .get(name)
+            .ok_or_else(|| format!("No music track named {}", name))?;
+        match &self.source {
+            AssetSource::Directory(dir) => Ok(dir.join(path)),
+            AssetSource::Archive(archive) => {
+                let file = std::env::temp_dir().join("personality").join(path);
+                let write = |bytes: Vec<u8>| {
+                    if let Some(parent) = file.parent() {
+                        std::fs::create_dir_all(parent)?;
+                    }
+                    std::fs::write(&file, bytes)
+                };
+                write(archive.read(path)?).map_err(|err| err.to_string())?;
+                Ok(file)
+            }
+        }
+    }
+
+    pub fn manifest(&self) -> &Manifest {
+        &self.manifest
+    }
+
+    pub fn source(&self) -> &AssetSource {
+        &self.source
+    }
+
+    pub fn errors(&self) -> Result<(), AssetErrors> {
+        if self.errors.is_empty() {
+            Ok(())
+        } else {
+            Err(AssetErrors(self.errors.clone()))
+        }
+    }
+
+    pub fn texture(&self, name: &str) -> Rc<Picture> {
+        lookup(&self.textures, name, &self.placeholder_texture)
+    }
+
+    pub fn animation(&self, name: &str) -> Rc<Animation> {
+        lookup(&self.animations, name, &self.placeholder_animation)
+    }
+
+    pub fn image(&self, name: &str) -> Rc<Image> {
+        lookup(&self.images, name, &self.placeholder_image)
+    }
+
+    /// Decoded samples of a sound effect; playable [`Sound`]s are made from them by the
+    /// audio system.
+    pub fn wave(&self, name: &str) -> Rc<Wave> {
+        lookup(&self.waves, name, &self.placeholder_wave)
+    }
+}
+
+fn check<T>(errors: &mut Vec<AssetError>, path: &str, result: Result<T, String>) -> Option<T> {
+    result
+        .map_err(|reason| {
+            errors.push(AssetError {
+                path: path.to_owned(),
+                reason,
+            })
+        })
+        .ok()
+}
+
+fn lookup<T>(map: &HashMap<String, Rc<T>>, name: &str, placeholder: &Rc<T>) -> Rc<T> {
+    map.get(name).unwrap_or(placeholder).clone()
+}
+
+fn extension(path: &str) -> String {
+    match path.rfind('.') {
+        Some(dot) => path[dot..].to_lowercase(),
+        None => String::new(),
+    }
+}
+
+pub fn load_image(source: &AssetSource, path: &str) -> Result<Image, String> {
+    let bytes = source.read(path)?;
+    Image::load_image_from_mem(&extension(path), &bytes, bytes.len() as _)
+}
+
+/// Uploads `image` as a texture when there is a window.
+fn picture(window: &mut Window, image: &Image) -> Result<Picture, String> {
+    match window {
+        Some((rl, thread)) => rl.load_texture_from_image(thread, image).map(Picture::new),
+        None => Ok(Picture::headless(image)),
+    }
+}
+
+fn placeholder_texture(window: &mut Window) -> Picture {
+    let image = Image::gen_image_checked(16, 16, 4, 4, Color::MAGENTA, Color::BLACK);
+    picture(window, &image).expect("Failed to create placeholder texture.")
+}
+
+pub fn wave_from_memory(file_type: &str, bytes: &[u8]) -> Result<Wave, String> {
+    let file_type = CString::new(file_type).map_err(|err| err.to_string())?;
+    let wave =
+        unsafe { ffi::LoadWaveFromMemory(file_type.as_ptr(), bytes.as_ptr(), bytes.len() as _) };
+    if wave.data.is_null() {
+        return Err("Wave data is null. Check provided buffer data".to_owned());
+    }
+    Ok(unsafe { Wave::from_raw(wave) })
+}
+
+/// A tenth of a second of 16-bit mono silence, built as an in-memory WAV file.
+fn silence() -> Wave {
+    let sample_rate = 22050u32;
+    let data_size = sample_rate / 10 * 2;
+
+    let mut bytes = Vec::with_capacity(44 + data_size as usize);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data_size).to_le_bytes());
+    bytes.extend_from_slice(b"WAVEfmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    bytes.extend_from_slice(&2u16.to_le_bytes());
+    bytes.extend_from_slice(&16u16.to_le_bytes());
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&data_size.to_le_bytes());
+    bytes.resize(44 + data_size as usize, 0);
+
+    wave_from_memory(".wav", &bytes).expect("Failed to create placeholder sound.")
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, SQRT_2};
+
+use crate::assets::*;
+use crate::ecs::Owner;
+use crate::events::*;
+use crate::resources::*;
+use crate::settings::*;
+
+/// Seconds it takes the music to fade between the calm and the tense track.
+const CROSSFADE: f32 = 1.5;
+const VOLUME_STEP: f32 = 0.1;
+/// Number of pre-panned copies of every effect, spread evenly from left to right.
+const PAN_STEPS: usize = 9;
+/// Volume of sounds far away from the camera, relative to ones on screen.
+const MIN_GAIN: f32 = 0.25;
+
+/// Name, maximum overlapping voices and random pitch variation of every sound effect.
+const EFFECTS: &[(&str, usize, f32)] = &[
+    ("Shoot", 4, 0.1),
+    ("Jump", 2, 0.05),
+    ("Hit", 3, 0.15),
+    ("RolesReversed", 1, 0.0),
+    ("GameOver", 1, 0.0),
+];
+
+struct Effect {
+    variants: Vec<Sound>,
+    duration: f32,
+    voices: Vec<f32>,
+    limit: usize,
+    pitch_variation: f32,
+}
+
+impl Effect {
+    fn new(wave: &Wave, limit: usize, pitch_variation: f32) -> Self {
+        let mut stereo = wave.wave_copy();
+        stereo.wave_format(wave.sampleRate as i32, 16, 2);
+        let variants = (0..PAN_STEPS)
+            .filter_map(|step| {
+                let mut panned = stereo.wave_copy();
+                pan(
+                    &mut panned,
+                    step as f32 / (PAN_STEPS - 1) as f32 * 2.0 - 1.0,
+                );
+                Sound::load_sound_from_wave(&panned).ok()
+            })
+            .collect();
+
+        let channels = wave.channels.max(1) as f32;
+        let sample_rate = wave.sampleRate.max(1) as f32;
+        Self {
+            duration: wave.sampleCount as f32 / channels / sample_rate,
+            variants,
+            voices: Vec::new(),
+            limit,
+            pitch_variation,
+        }
+    }
+}
+
+pub struct Audio {
+    device: RaylibAudio,
+    settings: AudioSettings,
+    effects: HashMap<&'static str, Effect>,
+
+    calm: Option<Music>,
+    tense: Option<Music>,
+    tension: f32,
+
+    listener: Vector2,
+    hearing: f32,
+}
+
+impl Audio {
+    pub fn new(
+        device: RaylibAudio,
+        thread: &RaylibThread,
+        assets: &Assets,
+        settings: AudioSettings,
+    ) -> Self {
+        let mut audio = Self {
+            device,
+            settings,
+            effects: HashMap::new(),
+
+            calm: None,
+            tense: None,
+            tension: 0.0,
+
+            listener: Vector2::zero(),
+            hearing: 1.0,
+        };
+        audio.reload(assets);
+        audio.reload_music(thread, assets);
+        audio
+    }
+
+    /// Restarts both music tracks from the current files.
+    pub fn reload_music(&mut self, thread: &RaylibThread, assets: &Assets) {
+        for music in [&mut self.calm, &mut self.tense].into_iter().flatten() {
+            self.device.stop_music_stream(music);
+        }
+        self.calm = load_music(thread, assets, "Calm");
+        self.tense = load_music(thread, assets, "Tense");
+        for music in [&mut self.calm, &mut self.tense].into_iter().flatten() {
+            self.device.play_music_stream(music);
+        }
+        self.apply_volume();
+    }
+
+    /// Picks up reloaded sound effects.
+    pub fn reload(&mut self, assets: &Assets) {
+        self.effects = EFFECTS
+            .iter()
+            .map(|&(name, limit, pitch_variation)| {
+                (
+                    name,
+                    Effect::new(&assets.wave(name), limit, pitch_variation),
+                )
+            })
+            .collect();
+    }
+
+    pub fn settings(&self) -> AudioSettings {
+        self.settings
+    }
+
+    fn apply_volume(&mut self) {
+        self.device.set_master_volume(self.settings.master);
+        let music = self.settings.music;
+        if let Some(calm) = &mut self.calm {
+            self.device
+                .set_music_volume(calm, music * (1.0 - self.tension));
+        }
+        if let Some(tense) = &mut self.tense {
+            self.device.set_music_volume(tense, music * self.tension);
+        }
+    }
+
+    /// Sounds are heard relative to `position` (the camera target), with `hearing` being
+    /// the distance from it to the edge of the screen in world units.
+    pub fn set_listener(&mut self, position: Vector2, hearing: f32) {
+        self.listener = position;
+        self.hearing = hearing.max(1.0);
+    }
+
+    /// Plays an effect panned and attenuated by where it happened, or centered at full
+    /// volume without a position.
+    pub fn play(&mut self, name: &str, position: Option<Vector2>) {
+        let effect = match self.effects.get_mut(name) {
+            Some(effect) if !effect.variants.is_empty() => effect,
+            _ => return,
+        };
+        if effect.voices.len() >= effect.limit {
+            return;
+        }
+
+        let (pan, gain) = match position {
+            Some(position) => {
+                let offset = (position - self.listener) / self.hearing;
+                let gain = 1.0 - (offset.length() - 1.0).max(0.0) / 2.0;
+                (offset.x.clamp(-1.0, 1.0), gain.max(MIN_GAIN))
+            }
+            None => (0.0, 1.0),
+        };
+        let step = ((pan + 1.0) / 2.0 * (PAN_STEPS - 1) as f32).round() as usize;
+        let sound = &effect.variants[step.min(effect.variants.len() - 1)];
+
+        let variation = (effect.pitch_variation * 100.0) as i32;
+        let pitch = 1.0 + get_random_value::<i32>(-variation, variation) as f32 / 100.0;
+        self.device
+            .set_sound_volume(sound, self.settings.sfx * gain);
+        self.device.set_sound_pitch(sound, pitch);
+        self.device.play_sound_multi(sound);
+        effect.voices.push(effect.duration / pitch);
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        let sound = match event {
+            GameEvent::BulletFired {
+                owner: Owner::Player,
+                ..
+            } => "Shoot",
+            GameEvent::PlayerJumped { .. } => "Jump",
+            GameEvent::TowerHit { .. } | GameEvent::PlayerKilled { .. } => "Hit",
+            GameEvent::RolesReversed { .. } => "RolesReversed",
+            GameEvent::GameOver => "GameOver",
+            _ => return,
+        };
+        self.play(sound, event.position());
+    }
+
+    /// Streams the music, fading towards the tense track while `tense` holds.
+    pub fn update(&mut self, dt: f32, tense: bool) {
+        for effect in self.effects.values_mut() {
+            effect.voices.retain_mut(|time| {
+                *time -= dt;
+                *time > 0.0
+            });
+        }
+
+        let target = if tense { 1.0 } else { 0.0 };
+        let step = dt / CROSSFADE;
+        self.tension += (target - self.tension).clamp(-step, step);
+        self.apply_volume();
+
+        for music in [&mut self.calm, &mut self.tense].into_iter().flatten() {
+            self.device.update_music_stream(music);
+        }
+    }
+
+    /// Volume hotkeys: `-`/`=` master, `[`/`]` music, `;`/`'` effects.
+    /// Returns whether anything changed, so the caller can persist the settings.
+    pub fn volume_controls(&mut self, rl: &RaylibHandle) -> bool {
+        let controls = [
+            (KeyboardKey::KEY_MINUS, KeyboardKey::KEY_EQUAL),
+            (
+                KeyboardKey::KEY_LEFT_BRACKET,
+                KeyboardKey::KEY_RIGHT_BRACKET,
+            ),
+            (KeyboardKey::KEY_SEMICOLON, KeyboardKey::KEY_APOSTROPHE),
+        ];
+
+        let mut changed = false;
+        for (bus, (down, up)) in controls.into_iter().enumerate() {
+            let step = (rl.is_key_pressed(up) as i32 - rl.is_key_pressed(down) as i32) as f32;
+            if step == 0.0 {
+                continue;
+            }
+            let volume = match bus {
+                0 => &mut self.settings.master,
+                1 => &mut self.settings.music,
+                _ => &mut self.settings.sfx,
+            };
+            *volume = (*volume + step * VOLUME_STEP).clamp(0.0, 1.0);
+            changed = true;
+        }
+        if changed {
+            self.apply_volume();
+        }
+        changed
+    }
+}
+
+fn load_music(thread: &RaylibThread, assets: &Assets, name: &str) -> Option<Music> {
+    let music = assets
+        .music_path(name)
+        .and_then(|path| Music::load_music_stream(thread, &path.to_string_lossy()));
+    match music {
+        Ok(music) => Some(music),
+        Err(err) => {
+            eprintln!("Failed to load music {}: {}", name, err);
+            None
+        }
+    }
+}
+
+/// Scales the channels of a 16-bit stereo wave with an equal-power pan law, `pan` going
+/// from -1 (left) to 1 (right).
+fn pan(wave: &mut Wave, pan: f32) {
+    let angle = (pan + 1.0) * PI / 4.0;
+    let gains = [
+        (angle.cos() * SQRT_2).min(1.0),
+        (angle.sin() * SQRT_2).min(1.0),
+    ];
+    let samples =
+        unsafe { std::slice::from_raw_parts_mut(wave.data as *mut i16, wave.sampleCount as usize) };
+    for (i, sample) in samples.iter_mut().enumerate() {
+        *sample = (*sample as f32 * gains[i % 2]) as i16;
+    }
+}
@@ -0,0 +1,299 @@
+use crate::difficulty::*;
+use crate::ecs::*;
+use crate::game::*;
+use crate::player::*;
+use crate::resources::*;
+use crate::scene::*;
+use crate::settings::*;
+
+/// Seconds between shots, about as fast as a person clicks.
+const FIRE_INTERVAL: f32 = 0.25;
+/// Seconds of walking into a wall before the bot jumps.
+const STUCK_TIME: f32 = 0.2;
+/// How close to its goal the bot stops walking.
+const GOAL_DISTANCE: f32 = 4.0;
+
+const USAGE: &str = "Usage: personality bot [--level name] [--strategy name] \
+[--difficulty name] [--adaptive] [--runs n]";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Stands still and never shoots, as a baseline.
+    Idle,
+    /// Shoots down bullets heading for the tower.
+    Intercept,
+    /// Only shoots at the tower while roles are reversed.
+    Attack,
+    /// Intercepts normally and attacks during reversals.
+    Balanced,
+}
+
+const STRATEGIES: &[(Strategy, &str)] = &[
+    (Strategy::Idle, "idle"),
+    (Strategy::Intercept, "intercept"),
+    (Strategy::Attack, "attack"),
+    (Strategy::Balanced, "balanced"),
+];
+
+impl Strategy {
+    pub fn name(self) -> &'static str {
+        STRATEGIES
+            .iter()
+            .find(|(strategy, _)| *strategy == self)
+            .map(|(_, name)| *name)
+            .unwrap_or_default()
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        STRATEGIES
+            .iter()
+            .find(|(_, other)| *other == name)
+            .map(|(strategy, _)| *strategy)
+    }
+}
+
+/// Plays the game through [`PlayerInput`], the same way a person does.
+pub struct Bot {
+    strategy: Strategy,
+    cooldown: f32,
+    stuck: f32,
+    /// Which end of the tower the next attack aims at.
+    low: bool,
+}
+
+impl Bot {
+    pub fn new(strategy: Strategy) -> Self {
+        Self {
+            strategy,
+            cooldown: 0.0,
+            stuck: 0.0,
+            low: false,
+        }
+    }
+
+    /// Decides this frame's input.
+    pub fn input(&mut self, dt: f32, scene: &Scene, player: &Player) -> PlayerInput {
+        self.cooldown -= dt;
+        let reversed = scene.tower().reversed();
+        let (target, goal) = match (self.strategy, reversed) {
+            (Strategy::Intercept | Strategy::Balanced, false) => match threat(scene) {
+                Some(bullet) => (
+                    Some(lead(scene, player, bullet)),
+                    scene.world.center(bullet),
+                ),
+                None => (None, None),
+            },
+            (Strategy::Attack | Strategy::Balanced, true) => (Some(self.attack(scene)), None),
+            _ => (None, None),
+        };
+
+        let tower = scene.tower().rect();
+        let mut input = PlayerInput {
+            aim: target.unwrap_or(rvec2(
+                tower.x + tower.width / 2.0,
+                tower.y + tower.height / 2.0,
+            )),
+            ..Default::default()
+        };
+        if target.is_some() && self.cooldown <= 0.0 {
+            input.shoot = true;
+            self.cooldown = FIRE_INTERVAL;
+            self.low = !self.low;
+        }
+
+        // * Movement, towards the threat but staying clear of the tower
+        if let Some(goal) = goal {
+            let zone = scene.tower_zone();
+            let center = player.center();
+            let x = if center.x < zone.x + zone.width / 2.0 {
+                goal.x.min(zone.x - player.size().x)
+            } else {
+                goal.x.max(zone.x + zone.width + player.size().x)
+            };
+            if (x - center.x).abs() > GOAL_DISTANCE {
+                input.direction = (x - center.x).signum();
+            }
+        }
+        let body = player.body();
+        if input.direction != 0.0 && body.grounded && body.velocity.x.abs() < 5.0 {
+            self.stuck += dt;
+        } else {
+            self.stuck = 0.0;
+        }
+        if self.stuck > STUCK_TIME {
+            input.jump = true;
+            self.stuck = 0.0;
+        }
+        input
+    }
+
+    /// Aims at the top and bottom of the reversed tower in turn, so the shield can't cover
+    /// both.
+    fn attack(&self, scene: &Scene) -> Vector2 {
+        let tower = scene.tower().rect();
+        let y = if !self.low {
+            tower.y + 4.0
+        } else {
+            tower.y + tower.height - 4.0
+        };
+        rvec2(tower.x + tower.width / 2.0, y)
+    }
+}
+
+/// The spawned bullet that will reach the tower first, if any is heading for it.
+fn threat(scene: &Scene) -> Option<Entity> {
+    let tower = scene.tower().rect();
+    let center = tower.x + tower.width / 2.0;
+    scene
+        .bullets()
+        .filter(|&bullet| scene.world.owners.get(bullet) == Some(&Owner::Spawner))
+        .filter_map(|bullet| {
+            let position = scene.world.center(bullet)?;
+            let Velocity(velocity) = *scene.world.velocities.get(bullet)?;
+            let time = (center - position.x) / velocity.x;
+            let y = position.y + velocity.y * time;
+            (time > 0.0 && y > tower.y && y < tower.y + tower.height).then_some((bullet, time))
+        })
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(bullet, _)| bullet)
+}
+
+/// Where to aim to hit `bullet`, accounting for its movement while the shot travels.
+fn lead(scene: &Scene, player: &Player, bullet: Entity) -> Vector2 {
+    let gun = player.position() + rvec2(5, 11);
+    let position = scene.world.center(bullet).unwrap_or_default();
+    let velocity = scene
+        .world
+        .velocities
+        .get(bullet)
+        .map(|velocity| velocity.0)
+        .unwrap_or_default();
+
+    let mut aim = position;
+    for _ in 0..3 {
+        let distance = (aim - gun).length();
+        // Matches the player's shot speed, which grows with the aim distance.
+        let speed = distance * 0.5 + 80.0;
+        aim = position + velocity * (distance / speed);
+    }
+    aim
+}
+
+/// Runs `personality bot`, printing the distribution of survival times for each level,
+/// difficulty and strategy.
+pub fn run(args: &[String]) -> bool {
+    let mut level = None;
+    let mut strategies = STRATEGIES
+        .iter()
+        .map(|(strategy, _)| *strategy)
+        .collect::<Vec<_>>();
+    let mut difficulties = vec![Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+    let mut adaptive = false;
+    let mut runs = 20;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--adaptive" {
+            adaptive = true;
+            continue;
+        }
+        let value = args.next();
+        match (arg.as_str(), value) {
+            ("--level", Some(name)) => level = Some(name.clone()),
+            ("--difficulty", Some(name)) => match Difficulty::parse(name) {
+                Some(difficulty) => difficulties = vec![difficulty],
+                None => {
+                    eprintln!("Unknown difficulty {}", name);
+                    return false;
+                }
+            },
+            ("--strategy", Some(name)) => match Strategy::parse(name) {
+                Some(strategy) => strategies = vec![strategy],
+                None => {
+                    eprintln!("Unknown strategy {}", name);
+                    return false;
+                }
+            },
+            ("--runs", Some(count)) => match count.parse() {
+                Ok(count) if count > 0 => runs = count,
+                _ => {
+                    eprintln!("Invalid run count {}", count);
+                    return false;
+                }
+            },
+            _ => {
+                eprintln!("{}", USAGE);
+                return false;
+            }
+        }
+    }
+
+    let (assets, levels) = match load_headless(level.as_deref()) {
+        Some(loaded) => loaded,
+        None => return false,
+    };
+
+    let custom = Settings::load().custom_difficulty;
+    for level in &levels {
+        for &difficulty in &difficulties {
+            for &strategy in &strategies {
+                let mut times = (1..=runs)
+                    .map(|seed| {
+                        let mut bot = Bot::new(strategy);
+                        play_headless(
+                            &assets,
+                            level,
+                            difficulty.tuning(&custom),
+                            adaptive,
+                            seed,
+                            |game| GameStep::new(bot.input(DT, &game.scene, &game.player)),
+                            |_| (),
+                        )
+                    })
+                    .collect::<Vec<_>>();
+                times.sort_by(f32::total_cmp);
+                println!(
+                    "Level {} on {}{} with {} bot, {} runs:",
+                    level.name,
+                    difficulty.name(),
+                    if adaptive { " (adaptive)" } else { "" },
+                    strategy.name(),
+                    runs
+                );
+                print_distribution(&times);
+            }
+        }
+    }
+    true
+}
+
+/// Prints summary statistics of sorted survival `times` and a histogram.
+fn print_distribution(times: &[f32]) {
+    let percentile = |p: f32| times[((times.len() - 1) as f32 * p).round() as usize];
+    let mean = times.iter().sum::<f32>() / times.len() as f32;
+    println!(
+        "  mean {:.1}s, min {:.1}s, p10 {:.1}s, median {:.1}s, p90 {:.1}s, max {:.1}s",
+        mean,
+        times[0],
+        percentile(0.1),
+        percentile(0.5),
+        percentile(0.9),
+        times[times.len() - 1]
+    );
+
+    let buckets = 10;
+    let width = (times[times.len() - 1] / buckets as f32).max(1.0);
+    let mut counts = vec![0; buckets];
+    for time in times {
+        counts[((time / width) as usize).min(buckets - 1)] += 1;
+    }
+    let most = counts.iter().copied().max().unwrap_or(1);
+    for (i, count) in counts.iter().enumerate() {
+        println!(
+            "  {:>6.1}s - {:>6.1}s | {} {}",
+            i as f32 * width,
+            (i + 1) as f32 * width,
+            "#".repeat(count * 40 / most),
+            count
+        );
+    }
+}
@@ -0,0 +1,139 @@
+use crate::events::*;
+use crate::resources::*;
+use crate::viewport::*;
+
+/// Seconds it takes the camera to cover most of the distance to where it wants to be.
+const SMOOTHING: f32 = 0.15;
+/// Half the size of the box around the camera the subject can move in without dragging it.
+const DEADZONE: Vector2 = Vector2 { x: 12.0, y: 8.0 };
+/// How far ahead of the subject the camera leans towards the aim, at most.
+const LOOK_AHEAD: f32 = 24.0;
+/// Fraction of the distance to the aim point used as look-ahead.
+const LOOK_AHEAD_RATE: f32 = 0.25;
+/// Seconds the camera stays on the tower when roles reverse.
+const FOCUS_TIME: f32 = 1.2;
+/// Zoom lost per second after a punch.
+const PUNCH_DECAY: f32 = 0.8;
+/// Largest camera offset at full trauma, in native pixels.
+const MAX_SHAKE: f32 = 4.0;
+/// Trauma lost per second.
+const SHAKE_DECAY: f32 = 1.5;
+
+/// Follows a subject through the level with smoothing, a deadzone and look-ahead towards
+/// where it aims, and reacts to gameplay events with focus shots, zoom punches and shake.
+pub struct CameraController {
+    camera: Camera2D,
+    position: Vector2,
+    focus: Option<(Vector2, f32)>,
+    punch: f32,
+    trauma: f32,
+}
+
+impl CameraController {
+    pub fn new(target: Vector2) -> Self {
+        Self {
+            camera: Camera2D {
+                target,
+                offset: Vector2::zero(),
+                rotation: 0.0,
+                zoom: 1.0,
+            },
+            position: target,
+            focus: None,
+            punch: 0.0,
+            trauma: 0.0,
+        }
+    }
+
+    /// Looks at `target` for `time` seconds, ignoring the subject.
+    pub fn focus(&mut self, target: Vector2, time: f32) {
+        self.focus = Some((target, time));
+    }
+
+    /// Briefly zooms in by `amount` (0.1 is 10%).
+    pub fn punch(&mut self, amount: f32) {
+        self.punch = self.punch.max(amount);
+    }
+
+    /// Adds trauma; the shake grows with its square.
+    pub fn shake(&mut self, trauma: f32) {
+        self.trauma = (self.trauma + trauma).min(1.0);
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::ShieldHit { .. } => self.shake(0.15),
+            GameEvent::TowerHit { reversed, .. } => {
+                self.shake(if reversed { 0.2 } else { 0.3 });
+            }
+            GameEvent::BulletsAnnihilated { .. } => self.shake(0.1),
+            GameEvent::PlayerKilled { .. } => self.shake(0.4),
+            GameEvent::RolesReversed { position, .. } => {
+                self.focus(position, FOCUS_TIME);
+                self.punch(0.15);
+                self.shake(0.6);
+            }
+            _ => (),
+        }
+    }
+
+    /// Moves towards `subject`, leaning towards `aim`, and keeps the view inside a level of
+    /// `bounds` size. Runs on real time, so it keeps moving during hit-stop.
+    pub fn update(
+        &mut self,
+        dt: f32,
+        viewport: &Viewport,
+        bounds: Vector2,
+        subject: Vector2,
+        aim: Vector2,
+    ) {
+        let mut goal = self.position;
+        if let Some((target, timer)) = &mut self.focus {
+            goal = *target;
+            *timer -= dt;
+            if *timer <= 0.0 {
+                self.focus = None;
+            }
+        } else {
+            let lean = aim - subject;
+            let mut subject = subject;
+            if lean.length() > 0.0 {
+                subject += lean.normalized() * (lean.length() * LOOK_AHEAD_RATE).min(LOOK_AHEAD);
+            }
+            let delta = subject - self.position;
+            goal.x += delta.x - delta.x.clamp(-DEADZONE.x, DEADZONE.x);
+            goal.y += delta.y - delta.y.clamp(-DEADZONE.y, DEADZONE.y);
+        }
+        self.position += (goal - self.position) * (1.0 - (-dt / SMOOTHING).exp());
+
+        self.punch = (self.punch - PUNCH_DECAY * dt).max(0.0);
+        self.trauma = (self.trauma - SHAKE_DECAY * dt).max(0.0);
+
+        // The world is rendered at native resolution, so outside of punches the camera
+        // doesn't zoom and only moves in whole pixels.
+        let view = viewport.size();
+        self.camera.zoom = 1.0 + self.punch;
+        self.camera.offset = rvec2((view.x / 2.0).floor(), (view.y / 2.0).floor());
+
+        let half = self.camera.offset / self.camera.zoom;
+        let far = (view - self.camera.offset) / self.camera.zoom;
+        self.position.x = self
+            .position
+            .x
+            .clamp(half.x, (bounds.x - far.x).max(half.x));
+        self.position.y = self
+            .position
+            .y
+            .clamp(half.y, (bounds.y - far.y).max(half.y));
+
+        let amount = self.trauma * self.trauma * MAX_SHAKE;
+        self.camera.target = rvec2(
+            (self.position.x + random(-1.0, 1.0) * amount).round(),
+            (self.position.y + random(-1.0, 1.0) * amount).round(),
+        );
+    }
+
+    pub fn camera(&self) -> Camera2D {
+        self.camera
+    }
+}
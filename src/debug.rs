@@ -0,0 +1,221 @@
+use crate::ecs::*;
+use crate::player::*;
+use crate::resources::*;
+use crate::scene::*;
+
+/// Lines of console output kept on screen.
+const LOG_LINES: usize = 8;
+/// Seconds of bullet travel drawn as its velocity vector.
+const VELOCITY_SCALE: f32 = 0.25;
+
+const HELP: &[&str] = &[
+    "spawn <x> <y> <vx> <vy> - spawn a bullet",
+    "reverse - reverse roles now",
+    "health <0-1> - set the tower health",
+    "timescale <scale> - slow down or speed up the game",
+    "teleport <x> <y> - move the player",
+];
+
+/// Console commands that change the game, applied by the caller.
+#[derive(Clone, Copy, Debug)]
+pub enum Command {
+    Spawn {
+        position: Vector2,
+        velocity: Vector2,
+    },
+    Reverse,
+    TowerHealth(f32),
+    Teleport(Vector2),
+}
+
+/// `F3` toggles an overlay of hitboxes, the collision mask, bullet velocities, shield
+/// targeting and timers; `` ` `` opens a console that pauses the game while typing.
+pub struct DebugTools {
+    pub overlay: bool,
+    console: bool,
+    input: String,
+    log: Vec<String>,
+    time_scale: f32,
+}
+
+impl Default for DebugTools {
+    fn default() -> Self {
+        Self {
+            overlay: false,
+            console: false,
+            input: String::new(),
+            log: vec!["Type help for a list of commands".to_owned()],
+            time_scale: 1.0,
+        }
+    }
+}
+
+impl DebugTools {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn console_open(&self) -> bool {
+        self.console
+    }
+
+    /// Multiplier for the simulation time step.
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Returns the command entered this frame, if any.
+    pub fn update(&mut self, rl: &RaylibHandle) -> Option<Command> {
+        if rl.is_key_pressed(KeyboardKey::KEY_F3) {
+            self.overlay = !self.overlay;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_GRAVE) {
+            self.console = !self.console;
+            self.input.clear();
+        }
+        if !self.console {
+            return None;
+        }
+
+        loop {
+            let char = unsafe { ffi::GetCharPressed() };
+            if char <= 0 {
+                break;
+            }
+            match char::from_u32(char as u32) {
+                Some('`') | None => (),
+                Some(char) => self.input.push(char),
+            }
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_BACKSPACE) {
+            self.input.pop();
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_ENTER) {
+            let line = std::mem::take(&mut self.input);
+            self.print(format!("> {}", line));
+            return self.execute(&line);
+        }
+        None
+    }
+
+    fn print(&mut self, line: String) {
+        self.log.push(line);
+        let excess = self.log.len().saturating_sub(LOG_LINES);
+        self.log.drain(..excess);
+    }
+
+    fn execute(&mut self, line: &str) -> Option<Command> {
+        let mut words = line.split_whitespace();
+        let name = words.next()?;
+        let args = match words.map(str::parse::<f32>).collect::<Result<Vec<_>, _>>() {
+            Ok(args) => args,
+            Err(err) => {
+                self.print(format!("Invalid number: {}", err));
+                return None;
+            }
+        };
+
+        match (name, args.as_slice()) {
+            ("help", []) => {
+                for line in HELP {
+                    self.print((*line).to_owned());
+                }
+                None
+            }
+            ("spawn", &[x, y, vx, vy]) => Some(Command::Spawn {
+                position: rvec2(x, y),
+                velocity: rvec2(vx, vy),
+            }),
+            ("reverse", []) => Some(Command::Reverse),
+            ("health", &[health]) => Some(Command::TowerHealth(health)),
+            ("timescale", &[scale]) if scale > 0.0 => {
+                self.time_scale = scale;
+                None
+            }
+            ("teleport", &[x, y]) => Some(Command::Teleport(rvec2(x, y))),
+            _ => {
+                self.print(format!("Unknown command or wrong arguments: {}", line));
+                None
+            }
+        }
+    }
+
+    /// World-space part of the overlay, drawn with the camera.
+    pub(crate) fn draw_world(&self, d: &mut impl RaylibDraw, scene: &Scene, player: &Player) {
+        if !self.overlay {
+            return;
+        }
+
+        scene.draw_mask(d, Color::RED.fade(0.35));
+        for &zone in scene.kill_zones() {
+            d.draw_rectangle_lines_ex(zone, 1, Color::RED);
+        }
+
+        // * Hitboxes
+        for (entity, collider) in scene.world.colliders.iter() {
+            if let Some(rect) = scene.world.rect(entity) {
+                let color = match collider.layer {
+                    Layer::Projectile => Color::YELLOW,
+                    Layer::Tower => Color::LIME,
+                    Layer::Shield => Color::SKYBLUE,
+                };
+                d.draw_rectangle_lines_ex(rect, 1, color);
+            }
+        }
+        d.draw_rectangle_lines_ex(player.rect(), 1, Color::MAGENTA);
+
+        // * Bullet velocities
+        for bullet in scene.bullets() {
+            if let (Some(center), Some(Velocity(velocity))) = (
+                scene.world.center(bullet),
+                scene.world.velocities.get(bullet),
+            ) {
+                d.draw_line_v(center, center + *velocity * VELOCITY_SCALE, Color::ORANGE);
+            }
+        }
+
+        // * Shield targeting
+        if let Some(target) = scene.target() {
+            d.draw_circle_lines(target.x as i32, target.y as i32, 4.0, Color::RED);
+        }
+        if let (Some(shield), Some(rect)) = (scene.tower().shield(), scene.tower().shield_rect()) {
+            let center = rvec2(rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+            let goal = rvec2(center.x, shield.target() + rect.height / 2.0);
+            d.draw_line_v(center, goal, Color::RED);
+            if let Some(target) = scene.target() {
+                d.draw_line_v(goal, target, Color::RED.fade(0.5));
+            }
+        }
+    }
+
+    /// Timers and the console, drawn in screen space on top of everything.
+    pub fn draw(&self, d: &mut RaylibDrawHandle, timers: &[(&str, f32)]) {
+        if self.overlay {
+            let mut y = d.get_screen_height() / 2;
+            let mut line = |d: &mut RaylibDrawHandle, text: &str| {
+                d.draw_text(text, 12, y, 10, Color::RAYWHITE);
+                y += 12;
+            };
+            for (name, value) in timers {
+                line(d, &format!("{}: {:.2}", name, value));
+            }
+            line(d, &format!("time scale: {:.2}", self.time_scale));
+        }
+
+        if self.console {
+            let width = d.get_screen_width();
+            let height = (LOG_LINES as i32 + 1) * 12 + 8;
+            d.draw_rectangle(0, 0, width, height, Color::BLACK.fade(0.8));
+            for (i, line) in self.log.iter().enumerate() {
+                d.draw_text(line, 4, 4 + i as i32 * 12, 10, Color::LIGHTGRAY);
+            }
+            d.draw_text(
+                &format!("> {}_", self.input),
+                4,
+                4 + LOG_LINES as i32 * 12,
+                10,
+                Color::RAYWHITE,
+            );
+        }
+    }
+}
@@ -0,0 +1,226 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::ecs::*;
+use crate::events::*;
+use crate::score::*;
+
+/// Seconds of play the adaptive difficulty looks back over.
+const WINDOW: f32 = 20.0;
+/// Range the adaptive difficulty scales spawn rate and bullet speed within.
+const MIN_SCALE: f32 = 0.6;
+const MAX_SCALE: f32 = 1.6;
+/// How fast the adaptive scale moves towards its target, per second.
+const SCALE_RATE: f32 = 0.05;
+/// Tower hits within the window that count as struggling the most.
+const MAX_HITS: f32 = 10.0;
+/// Shortest time between spawned bullets, however the interval is tuned.
+pub const MIN_BULLET_INTERVAL: f32 = 0.05;
+
+/// Numbers a difficulty sets. Ranges are `[min, max]` and drawn from at random.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Tuning {
+    /// Tower health lost per hit.
+    pub tower_damage: f32,
+    /// Health of the reversed tower lost per hit.
+    pub reversed_damage: f32,
+    /// Seconds to bring the reversed tower down.
+    pub reversal_time: f32,
+    /// Seconds between spawned bullets.
+    pub bullet_interval: [f32; 2],
+    /// Horizontal speed of spawned bullets.
+    pub bullet_speed: [f32; 2],
+    /// Seconds until roles reverse, counted while they aren't.
+    pub reversal_countdown: [f32; 2],
+}
+
+impl Tuning {
+    /// Checks numbers that would hang or break the game, like a zero bullet interval.
+    pub fn check(&self) -> Result<(), String> {
+        for (name, value) in [
+            ("tower_damage", self.tower_damage),
+            ("reversed_damage", self.reversed_damage),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                return Err(format!("Invalid {} {}", name, value));
+            }
+        }
+        if !(self.reversal_time > 0.0 && self.reversal_time.is_finite()) {
+            return Err(format!("Invalid reversal_time {}", self.reversal_time));
+        }
+        for (name, range) in [
+            ("bullet_interval", self.bullet_interval),
+            ("bullet_speed", self.bullet_speed),
+            ("reversal_countdown", self.reversal_countdown),
+        ] {
+            check_range(range).map_err(|err| format!("{} for {}", err, name))?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            tower_damage: 1.0 / 100.0,
+            reversed_damage: 1.0 / 10.0,
+            reversal_time: 10.0,
+            bullet_interval: [1.0, 2.0],
+            bullet_speed: [10.0, 200.0],
+            reversal_countdown: [10.0, 20.0],
+        }
+    }
+}
+
+/// Checks a `[min, max]` range from [`Tuning`]: not negative, in order and not all zero.
+pub fn check_range([min, max]: [f32; 2]) -> Result<(), String> {
+    if !(min >= 0.0 && min <= max && max > 0.0 && max.is_finite()) {
+        return Err(format!("Invalid range {}-{}", min, max));
+    }
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    /// Uses the tuning from the settings file.
+    Custom,
+}
+
+const DIFFICULTIES: &[(Difficulty, &str)] = &[
+    (Difficulty::Easy, "easy"),
+    (Difficulty::Normal, "normal"),
+    (Difficulty::Hard, "hard"),
+    (Difficulty::Custom, "custom"),
+];
+
+impl Difficulty {
+    pub fn name(self) -> &'static str {
+        DIFFICULTIES
+            .iter()
+            .find(|(difficulty, _)| *difficulty == self)
+            .map(|(_, name)| *name)
+            .unwrap_or_default()
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        DIFFICULTIES
+            .iter()
+            .find(|(_, other)| *other == name)
+            .map(|(difficulty, _)| *difficulty)
+    }
+
+    pub fn next(self) -> Self {
+        let index = DIFFICULTIES
+            .iter()
+            .position(|(difficulty, _)| *difficulty == self)
+            .unwrap_or_default();
+        DIFFICULTIES[(index + 1) % DIFFICULTIES.len()].0
+    }
+
+    pub fn tuning(self, custom: &Tuning) -> Tuning {
+        match self {
+            Self::Easy => Tuning {
+                tower_damage: 1.0 / 150.0,
+                reversed_damage: 1.0 / 7.0,
+                reversal_time: 14.0,
+                bullet_interval: [1.5, 2.5],
+                bullet_speed: [10.0, 150.0],
+                reversal_countdown: [15.0, 25.0],
+            },
+            Self::Normal => Tuning::default(),
+            Self::Hard => Tuning {
+                tower_damage: 1.0 / 70.0,
+                reversed_damage: 1.0 / 14.0,
+                reversal_time: 8.0,
+                bullet_interval: [0.7, 1.4],
+                bullet_speed: [20.0, 240.0],
+                reversal_countdown: [8.0, 15.0],
+            },
+            Self::Custom => *custom,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Sample {
+    TowerHit,
+    Shot,
+    Interception,
+}
+
+/// Speeds up bullets and spawns them more often while the player intercepts well and the
+/// tower takes little damage, and backs off when they struggle.
+pub struct Adaptive {
+    samples: VecDeque<(f32, Sample)>,
+    time: f32,
+    scale: f32,
+}
+
+impl Default for Adaptive {
+    fn default() -> Self {
+        Self {
+            samples: VecDeque::new(),
+            time: 0.0,
+            scale: 1.0,
+        }
+    }
+}
+
+impl Adaptive {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        let sample = match event {
+            GameEvent::TowerHit {
+                reversed: false, ..
+            } => Sample::TowerHit,
+            GameEvent::BulletFired {
+                owner: Owner::Player,
+                ..
+            } => Sample::Shot,
+            // Scored rather than every annihilation, since only those need a player bullet.
+            GameEvent::Scored {
+                kind: ScoreKind::Interception | ScoreKind::Annihilation,
+                ..
+            } => Sample::Interception,
+            _ => return,
+        };
+        self.samples.push_back((self.time, sample));
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        while let Some(&(time, _)) = self.samples.front() {
+            if time >= self.time - WINDOW {
+                break;
+            }
+            self.samples.pop_front();
+        }
+
+        let count = |kind| {
+            self.samples
+                .iter()
+                .filter(|(_, sample)| *sample == kind)
+                .count() as f32
+        };
+        let accuracy = count(Sample::Interception) / count(Sample::Shot).max(1.0);
+        let damage = (count(Sample::TowerHit) / MAX_HITS).min(1.0);
+        let target = (1.0 + accuracy.min(1.0) * 0.6 - damage * 0.6).clamp(MIN_SCALE, MAX_SCALE);
+        let step = SCALE_RATE * dt;
+        self.scale += (target - self.scale).clamp(-step, step);
+    }
+
+    /// Multiplier for the bullet spawn rate and speed.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+}
@@ -0,0 +1,279 @@
+use std::rc::Rc;
+
+use crate::resources::*;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Entity {
+    index: usize,
+    generation: u32,
+}
+
+impl Entity {
+    fn new(index: usize, generation: u32) -> Self {
+        Self { index, generation }
+    }
+}
+
+// * Components
+#[derive(Clone, Copy, Debug)]
+pub struct Position(pub Vector2);
+
+#[derive(Clone, Copy, Debug)]
+pub struct Velocity(pub Vector2);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layer {
+    Projectile,
+    Tower,
+    Shield,
+}
+
+impl Layer {
+    pub fn interacts(self, other: Layer) -> bool {
+        self == Layer::Projectile || other == Layer::Projectile
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Collider {
+    pub size: Vector2,
+    pub layer: Layer,
+}
+
+#[derive(Clone)]
+pub struct Sprite {
+    pub texture: Rc<Picture>,
+    pub flip: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Owner {
+    Player,
+    Spawner,
+    Scene,
+}
+
+/// What happens to an entity once it leaves the scene bounds.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutOfBounds {
+    /// Bounce off the top and bottom edges with a `1 / chance` probability.
+    Ricochet { chance: i32 },
+}
+
+// * Storage
+pub struct Storage<T> {
+    data: Vec<Option<(u32, T)>>,
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Self { data: Vec::new() }
+    }
+}
+
+impl<T> Storage<T> {
+    pub fn insert(&mut self, entity: Entity, value: T) {
+        if self.data.len() <= entity.index {
+            self.data.resize_with(entity.index + 1, || None);
+        }
+        self.data[entity.index] = Some((entity.generation, value));
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        match self.data.get(entity.index) {
+            Some(Some((generation, _))) if *generation == entity.generation => {
+                self.data[entity.index].take().map(|(_, value)| value)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.data.get(entity.index)? {
+            Some((generation, value)) if *generation == entity.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.data.get_mut(entity.index)? {
+            Some((generation, value)) if *generation == entity.generation => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.data.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref()
+                .map(|(generation, value)| (Entity::new(index, *generation), value))
+        })
+    }
+}
+
+// * World
+#[derive(Default)]
+pub struct World {
+    generations: Vec<u32>,
+    alive: Vec<bool>,
+    free: Vec<usize>,
+
+    pub positions: Storage<Position>,
+    pub velocities: Storage<Velocity>,
+    pub colliders: Storage<Collider>,
+    pub sprites: Storage<Sprite>,
+    pub owners: Storage<Owner>,
+    pub bounds: Storage<OutOfBounds>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn spawn(&mut self) -> Entity {
+        if let Some(index) = self.free.pop() {
+            self.alive[index] = true;
+            Entity::new(index, self.generations[index])
+        } else {
+            self.generations.push(0);
+            self.alive.push(true);
+            Entity::new(self.alive.len() - 1, 0)
+        }
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        if !self.is_alive(entity) {
+            return;
+        }
+        self.positions.remove(entity);
+        self.velocities.remove(entity);
+        self.colliders.remove(entity);
+        self.sprites.remove(entity);
+        self.owners.remove(entity);
+        self.bounds.remove(entity);
+
+        self.alive[entity.index] = false;
+        self.generations[entity.index] += 1;
+        self.free.push(entity.index);
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.alive.get(entity.index).copied().unwrap_or(false)
+            && self.generations[entity.index] == entity.generation
+    }
+
+    pub fn position(&self, entity: Entity) -> Option<Vector2> {
+        self.positions.get(entity).map(|position| position.0)
+    }
+
+    pub fn rect(&self, entity: Entity) -> Option<Rectangle> {
+        let position = self.position(entity)?;
+        let collider = self.colliders.get(entity)?;
+        Some(rrect(
+            position.x,
+            position.y,
+            collider.size.x,
+            collider.size.y,
+        ))
+    }
+
+    pub fn center(&self, entity: Entity) -> Option<Vector2> {
+        let rect = self.rect(entity)?;
+        Some(rvec2(rect.x + rect.width / 2.0, rect.y + rect.height / 2.0))
+    }
+
+    pub fn with_layer(&self, layer: Layer) -> impl Iterator<Item = Entity> + '_ {
+        self.colliders
+            .iter()
+            .filter(move |(_, collider)| collider.layer == layer)
+            .map(|(entity, _)| entity)
+    }
+}
+
+// * Systems
+pub mod systems {
+    use super::*;
+
+    pub fn movement(world: &mut World, dt: f32) {
+        let World {
+            positions,
+            velocities,
+            ..
+        } = world;
+        for (entity, velocity) in velocities.iter() {
+            if let Some(position) = positions.get_mut(entity) {
+                position.0 += velocity.0 * dt;
+            }
+        }
+    }
+
+    pub fn bounds(world: &mut World, area: Rectangle, rng: &mut Rng) {
+        let mut dead = Vec::new();
+        for (entity, policy) in world.bounds.iter() {
+            let rect = match world.rect(entity) {
+                Some(rect) => rect,
+                None => continue,
+            };
+            if area.check_collision_recs(&rect) {
+                continue;
+            }
+            let OutOfBounds::Ricochet { chance } = *policy;
+            let vertical = rect.y < area.y || rect.y + rect.height > area.y + area.height;
+            if vertical && rng.int(0, chance) == 0 {
+                let position = world.positions.get_mut(entity).unwrap();
+                position.0.y = if rect.y < area.y {
+                    area.y
+                } else {
+                    area.y + area.height - rect.height
+                };
+                if let Some(velocity) = world.velocities.get_mut(entity) {
+                    velocity.0.y *= -1.0;
+                }
+            } else {
+                dead.push(entity);
+            }
+        }
+        for entity in dead {
+            world.despawn(entity);
+        }
+    }
+
+    /// All overlapping collider pairs whose layers interact, in spawn order.
+    pub fn contacts(world: &World) -> Vec<(Entity, Entity)> {
+        let colliders = world
+            .colliders
+            .iter()
+            .filter_map(|(entity, collider)| Some((entity, collider.layer, world.rect(entity)?)))
+            .collect::<Vec<_>>();
+
+        let mut contacts = Vec::new();
+        for (i, (a, layer_a, rect_a)) in colliders.iter().enumerate() {
+            for (b, layer_b, rect_b) in &colliders[i + 1..] {
+                if layer_a.interacts(*layer_b) && rect_a.check_collision_recs(rect_b) {
+                    contacts.push((*a, *b));
+                }
+            }
+        }
+        contacts
+    }
+
+    pub fn draw_sprites(world: &World, d: &mut impl RaylibDraw) {
+        for (entity, sprite) in world.sprites.iter() {
+            if let (Some(position), Some(texture)) =
+                (world.position(entity), sprite.texture.texture())
+            {
+                d.draw_texture_rec(
+                    texture,
+                    rrect(
+                        0,
+                        0,
+                        texture.width() * if sprite.flip { -1 } else { 1 },
+                        texture.height(),
+                    ),
+                    position,
+                    Color::WHITE,
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,387 @@
+use std::ffi::CString;
+
+use crate::assets::*;
+use crate::manifest::*;
+use crate::resources::*;
+use crate::scene::*;
+use crate::viewport::*;
+
+/// Pixels per second the view pans at.
+const PAN_SPEED: f32 = 150.0;
+const MAX_BRUSH: i32 = 8;
+/// Seconds status messages stay on screen.
+const STATUS_TIME: f32 = 3.0;
+/// Width of the lane markers at the level edges.
+const LANE_WIDTH: f32 = 4.0;
+/// How close a right click has to be to a checkpoint to remove it.
+const CHECKPOINT_RADIUS: f32 = 6.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tool {
+    Paint,
+    Erase,
+    Tower,
+    Spawn,
+    Lane,
+    Checkpoint,
+    KillZone,
+}
+
+const TOOLS: &[(Tool, KeyboardKey, &str)] = &[
+    (Tool::Paint, KeyboardKey::KEY_ONE, "paint"),
+    (Tool::Erase, KeyboardKey::KEY_TWO, "erase"),
+    (Tool::Tower, KeyboardKey::KEY_THREE, "tower"),
+    (Tool::Spawn, KeyboardKey::KEY_FOUR, "spawn"),
+    (Tool::Lane, KeyboardKey::KEY_FIVE, "lane"),
+    (Tool::Checkpoint, KeyboardKey::KEY_SIX, "checkpoint"),
+    (Tool::KillZone, KeyboardKey::KEY_SEVEN, "kill zone"),
+];
+
+/// Edits the level being played: paints the collision mask straight into the scene and
+/// keeps a draft of the level's tower, spawn, bullet lanes, checkpoints and kill zones, which
+/// take effect on the next test-play and are written to the asset directory on save.
+pub struct Editor {
+    level: Option<LevelInfo>,
+    tool: Tool,
+    brush: i32,
+    view: Vector2,
+    drag_start: Option<Vector2>,
+    status: Option<(String, f32)>,
+}
+
+impl Editor {
+    pub fn new() -> Self {
+        Self {
+            level: None,
+            tool: Tool::Paint,
+            brush: 1,
+            view: Vector2::zero(),
+            drag_start: None,
+            status: None,
+        }
+    }
+
+    pub fn active(&self) -> bool {
+        self.level.is_some()
+    }
+
+    /// Starts editing `level`, looking at `view`.
+    pub fn open(&mut self, level: &LevelInfo, view: Vector2) {
+        self.level = Some(level.clone());
+        self.view = view;
+        self.drag_start = None;
+    }
+
+    /// Stops editing and returns the edited level, to test-play it.
+    pub fn close(&mut self) -> Option<LevelInfo> {
+        self.level.take()
+    }
+
+    /// Where the camera should look while editing.
+    pub fn view(&self) -> Vector2 {
+        self.view
+    }
+
+    fn set_status(&mut self, status: String) {
+        self.status = Some((status, STATUS_TIME));
+    }
+
+    pub fn update(
+        &mut self,
+        rl: &RaylibHandle,
+        assets: &Assets,
+        viewport: &Viewport,
+        camera: Camera2D,
+        scene: &mut Scene,
+        player_size: Vector2,
+    ) {
+        let dt = rl.get_frame_time();
+        if let Some((_, timer)) = &mut self.status {
+            *timer -= dt;
+            if *timer <= 0.0 {
+                self.status = None;
+            }
+        }
+        let level = match &mut self.level {
+            Some(level) => level,
+            None => return,
+        };
+
+        // * View, held still while Ctrl is down so Ctrl+S doesn't pan
+        let control = rl.is_key_down(KeyboardKey::KEY_LEFT_CONTROL);
+        if !control {
+            let pan = rvec2(
+                rl.is_key_down(KeyboardKey::KEY_D) as i32
+                    - rl.is_key_down(KeyboardKey::KEY_A) as i32,
+                rl.is_key_down(KeyboardKey::KEY_S) as i32
+                    - rl.is_key_down(KeyboardKey::KEY_W) as i32,
+            );
+            self.view += pan * PAN_SPEED * dt;
+        }
+        self.view.x = self.view.x.clamp(0.0, scene.width() as f32);
+        self.view.y = self.view.y.clamp(0.0, scene.height() as f32);
+
+        // * Tools
+        for &(tool, key, _) in TOOLS {
+            if rl.is_key_pressed(key) {
+                self.tool = tool;
+                self.drag_start = None;
+            }
+        }
+        let wheel = rl.get_mouse_wheel_move();
+        if wheel != 0.0 {
+            self.brush = (self.brush + wheel.signum() as i32).clamp(1, MAX_BRUSH);
+        }
+
+        let mouse = viewport.screen_to_world(rl.get_mouse_position(), camera);
+        let mouse = rvec2(mouse.x.floor(), mouse.y.floor());
+        let side = if mouse.x < scene.width() as f32 / 2.0 {
+            Side::Left
+        } else {
+            Side::Right
+        };
+        let pressed = rl.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON);
+        let released = rl.is_mouse_button_released(MouseButton::MOUSE_LEFT_BUTTON);
+        let remove = rl.is_mouse_button_pressed(MouseButton::MOUSE_RIGHT_BUTTON);
+        match self.tool {
+            Tool::Paint | Tool::Erase
+                if rl.is_mouse_button_down(MouseButton::MOUSE_LEFT_BUTTON) =>
+            {
+                let color = if self.tool == Tool::Paint {
+                    Color::BLACK
+                } else {
+                    Color::BLANK
+                };
+                let corner = mouse - (self.brush / 2) as f32;
+                for y in 0..self.brush {
+                    for x in 0..self.brush {
+                        scene.paint(corner + rvec2(x, y), color);
+                    }
+                }
+            }
+            Tool::Tower if pressed => {
+                let position = mouse - scene.tower().size() / 2.0;
+                level.tower_position = [position.x.round(), position.y.round()];
+            }
+            Tool::Spawn if pressed => {
+                let position = mouse - player_size / 2.0;
+                level.spawn = [position.x.round(), position.y.round()];
+            }
+            Tool::Lane => {
+                if pressed {
+                    self.drag_start = Some(mouse);
+                }
+                if released {
+                    if let Some(start) = self.drag_start.take() {
+                        level.lanes.push(LaneInfo {
+                            side,
+                            top: start.y.min(mouse.y).max(0.0),
+                            bottom: start.y.max(mouse.y).min(scene.height() as f32),
+                        });
+                    }
+                }
+                if remove {
+                    level.lanes.retain(|lane| {
+                        lane.side != side || mouse.y < lane.top || mouse.y > lane.bottom
+                    });
+                }
+            }
+            Tool::Checkpoint => {
+                if pressed {
+                    level.checkpoints.push([mouse.x, mouse.y]);
+                }
+                if remove {
+                    level
+                        .checkpoints
+                        .retain(|&[x, y]| rvec2(x, y).distance_to(mouse) > CHECKPOINT_RADIUS);
+                }
+            }
+            Tool::KillZone => {
+                if pressed {
+                    self.drag_start = Some(mouse);
+                }
+                if released {
+                    if let Some(start) = self.drag_start.take() {
+                        let zone = drag_rect(start, mouse);
+                        if zone.width > 0.0 && zone.height > 0.0 {
+                            level
+                                .kill_zones
+                                .push([zone.x, zone.y, zone.width, zone.height]);
+                        }
+                    }
+                }
+                if remove {
+                    level.kill_zones.retain(|&[x, y, width, height]| {
+                        !rrect(x, y, width, height).check_collision_point_rec(mouse)
+                    });
+                }
+            }
+            _ => (),
+        }
+
+        // * Save
+        if control && rl.is_key_pressed(KeyboardKey::KEY_S) {
+            let status = match save(assets, level, scene) {
+                Ok(()) => format!("Saved level {}", level.name),
+                Err(err) => format!("Failed to save level: {}", err),
+            };
+            self.set_status(status);
+        }
+    }
+
+    pub(crate) fn draw_world(
+        &self,
+        d: &mut impl RaylibDraw,
+        scene: &Scene,
+        player_size: Vector2,
+        mouse: Vector2,
+    ) {
+        let level = match &self.level {
+            Some(level) => level,
+            None => return,
+        };
+        scene.draw_mask(d, Color::RED.fade(0.4));
+
+        let tower = level.tower_position();
+        let size = scene.tower().size();
+        d.draw_rectangle_lines_ex(rrect(tower.x, tower.y, size.x, size.y), 1, Color::LIME);
+        let spawn = level.spawn();
+        d.draw_rectangle_lines_ex(
+            rrect(spawn.x, spawn.y, player_size.x, player_size.y),
+            1,
+            Color::MAGENTA,
+        );
+
+        let lane_rect = |side: Side, top: f32, bottom: f32| {
+            let x = match side {
+                Side::Left => 0.0,
+                Side::Right => scene.width() as f32 - LANE_WIDTH,
+            };
+            rrect(x, top, LANE_WIDTH, bottom - top)
+        };
+        for lane in &level.lanes {
+            d.draw_rectangle_rec(
+                lane_rect(lane.side, lane.top, lane.bottom),
+                Color::ORANGE.fade(0.6),
+            );
+        }
+        for &[x, y] in &level.checkpoints {
+            d.draw_circle_lines(x as i32, y as i32, CHECKPOINT_RADIUS, Color::LIME);
+        }
+        for zone in level.kill_zones() {
+            d.draw_rectangle_rec(zone, Color::RED.fade(0.3));
+            d.draw_rectangle_lines_ex(zone, 1, Color::RED);
+        }
+
+        if let (Some(start), Tool::KillZone) = (self.drag_start, self.tool) {
+            d.draw_rectangle_lines_ex(drag_rect(start, mouse), 1, Color::YELLOW);
+        } else if let Some(start) = self.drag_start {
+            let side = if mouse.x < scene.width() as f32 / 2.0 {
+                Side::Left
+            } else {
+                Side::Right
+            };
+            d.draw_rectangle_rec(
+                lane_rect(side, start.y.min(mouse.y), start.y.max(mouse.y)),
+                Color::YELLOW.fade(0.6),
+            );
+        }
+
+        let brush = match self.tool {
+            Tool::Paint | Tool::Erase => self.brush,
+            _ => 1,
+        };
+        let corner = rvec2(mouse.x.floor(), mouse.y.floor()) - (brush / 2) as f32;
+        d.draw_rectangle_lines_ex(rrect(corner.x, corner.y, brush, brush), 1, Color::WHITE);
+    }
+
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        if !self.active() {
+            return;
+        }
+        let tools = TOOLS
+            .iter()
+            .enumerate()
+            .map(|(i, (tool, _, name))| {
+                if *tool == self.tool {
+                    format!("[{} {}]", i + 1, name)
+                } else {
+                    format!("{} {}", i + 1, name)
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("  ");
+        let height = d.get_screen_height();
+        d.draw_rectangle(
+            0,
+            height - 40,
+            d.get_screen_width(),
+            40,
+            Color::BLACK.fade(0.7),
+        );
+        d.draw_text(&tools, 8, height - 36, 10, Color::RAYWHITE);
+        d.draw_text(
+            &format!(
+                "brush {} (wheel)  WASD pan  right click removes  Ctrl+S save  F4 test-play",
+                self.brush
+            ),
+            8,
+            height - 22,
+            10,
+            Color::LIGHTGRAY,
+        );
+        if let Some((status, _)) = &self.status {
+            d.draw_text(status, 8, height - 56, 10, Color::GOLD);
+        }
+    }
+}
+
+impl Default for Editor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The rectangle spanned by dragging from `start` to `end`.
+fn drag_rect(start: Vector2, end: Vector2) -> Rectangle {
+    rrect(
+        start.x.min(end.x),
+        start.y.min(end.y),
+        (end.x - start.x).abs(),
+        (end.y - start.y).abs(),
+    )
+}
+
+/// Writes the collision mask over the level's metadata image and the level into the
+/// manifest. Only works when the assets come from the asset directory.
+fn save(assets: &Assets, level: &LevelInfo, scene: &Scene) -> Result<(), String> {
+    let dir = match assets.source() {
+        AssetSource::Directory(dir) => dir,
+        AssetSource::Archive(_) => {
+            return Err("levels can only be saved to the asset directory".to_owned())
+        }
+    };
+
+    let mut manifest = assets.manifest().clone();
+    let mask = manifest
+        .images
+        .get(&level.metadata)
+        .ok_or_else(|| format!("no image named {}", level.metadata))?;
+    let path = dir.join(mask);
+    let image = scene.metadata_image();
+    let file = CString::new(path.to_string_lossy().as_bytes()).map_err(|err| err.to_string())?;
+    if !unsafe { ffi::ExportImage(*image, file.as_ptr()) } {
+        return Err(format!("could not write {}", path.display()));
+    }
+
+    match manifest
+        .levels
+        .iter_mut()
+        .find(|other| other.name == level.name)
+    {
+        Some(other) => *other = level.clone(),
+        None => manifest.levels.push(level.clone()),
+    }
+    std::fs::write(dir.join(MANIFEST_PATH), manifest.to_json()? + "\n")
+        .map_err(|err| err.to_string())
+}
@@ -0,0 +1,194 @@
+use crate::ecs::Owner;
+use crate::events::*;
+use crate::resources::*;
+
+/// Describes a burst of particles.
+#[derive(Clone, Copy, Debug)]
+pub struct Emitter {
+    pub count: (i32, i32),
+    pub speed: (f32, f32),
+    /// Total angle of the cone particles are emitted in, in degrees.
+    pub spread: f32,
+    pub life: (f32, f32),
+    pub size: (f32, f32),
+    pub gravity: f32,
+    /// Fraction of the velocity kept every second.
+    pub drag: f32,
+    pub colors: &'static [Color],
+}
+
+pub const MUZZLE_FLASH: Emitter = Emitter {
+    count: (4, 6),
+    speed: (30.0, 80.0),
+    spread: 40.0,
+    life: (0.05, 0.12),
+    size: (1.0, 2.0),
+    gravity: 0.0,
+    drag: 0.05,
+    colors: &[Color::YELLOW, Color::ORANGE, Color::WHITE],
+};
+
+pub const SPARKS: Emitter = Emitter {
+    count: (6, 10),
+    speed: (40.0, 120.0),
+    spread: 360.0,
+    life: (0.1, 0.3),
+    size: (1.0, 1.0),
+    gravity: 200.0,
+    drag: 0.2,
+    colors: &[Color::SKYBLUE, Color::WHITE, Color::BLUE],
+};
+
+pub const DEBRIS: Emitter = Emitter {
+    count: (5, 8),
+    speed: (30.0, 90.0),
+    spread: 120.0,
+    life: (0.4, 0.8),
+    size: (1.0, 3.0),
+    gravity: 300.0,
+    drag: 0.5,
+    colors: &[Color::GRAY, Color::DARKGRAY, Color::BROWN],
+};
+
+pub const ANNIHILATION: Emitter = Emitter {
+    count: (10, 14),
+    speed: (20.0, 100.0),
+    spread: 360.0,
+    life: (0.15, 0.4),
+    size: (1.0, 2.0),
+    gravity: 0.0,
+    drag: 0.02,
+    colors: &[Color::WHITE, Color::GOLD, Color::ORANGE],
+};
+
+pub const DUST: Emitter = Emitter {
+    count: (4, 8),
+    speed: (10.0, 40.0),
+    spread: 160.0,
+    life: (0.2, 0.5),
+    size: (1.0, 2.0),
+    gravity: -20.0,
+    drag: 0.05,
+    colors: &[Color::LIGHTGRAY, Color::BEIGE],
+};
+
+#[derive(Clone, Copy, Debug)]
+struct Particle {
+    position: Vector2,
+    velocity: Vector2,
+    life: f32,
+    max_life: f32,
+    size: f32,
+    gravity: f32,
+    drag: f32,
+    color: Color,
+}
+
+/// Particles and hit-stop, both triggered by gameplay events. Purely cosmetic: nothing here
+/// feeds back into the simulation except the hit-stop pausing it.
+#[derive(Default)]
+pub struct Effects {
+    particles: Vec<Particle>,
+    hit_stop: f32,
+}
+
+impl Effects {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Emits a burst at `position` in a cone centered on `angle` (in degrees, 0 pointing right).
+    pub fn emit(&mut self, emitter: &Emitter, position: Vector2, angle: f32) {
+        for _ in 0..get_random_value::<i32>(emitter.count.0, emitter.count.1) {
+            let angle = (angle + random(-emitter.spread, emitter.spread) / 2.0).to_radians();
+            let speed = random(emitter.speed.0, emitter.speed.1);
+            let life = random(emitter.life.0, emitter.life.1);
+            let color = emitter.colors
+                [get_random_value::<i32>(0, emitter.colors.len() as i32 - 1) as usize];
+            self.particles.push(Particle {
+                position,
+                velocity: rvec2(angle.cos(), angle.sin()) * speed,
+                life,
+                max_life: life,
+                size: random(emitter.size.0, emitter.size.1).round(),
+                gravity: emitter.gravity,
+                drag: emitter.drag,
+                color,
+            });
+        }
+    }
+
+    /// Freezes the simulation for `time` seconds.
+    pub fn hit_stop(&mut self, time: f32) {
+        self.hit_stop = self.hit_stop.max(time);
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::BulletFired {
+                owner: Owner::Player,
+                position,
+                velocity,
+            } => {
+                self.emit(
+                    &MUZZLE_FLASH,
+                    position,
+                    velocity.y.atan2(velocity.x).to_degrees(),
+                );
+            }
+            GameEvent::ShieldHit { position } => {
+                self.emit(&SPARKS, position, 0.0);
+            }
+            GameEvent::TowerHit { position, reversed } => {
+                self.emit(&DEBRIS, position, -90.0);
+                self.hit_stop(if reversed { 0.03 } else { 0.06 });
+            }
+            GameEvent::BulletsAnnihilated { position } => {
+                self.emit(&ANNIHILATION, position, 0.0);
+                self.hit_stop(0.02);
+            }
+            GameEvent::PlayerLanded { position, speed } if speed > 150.0 => {
+                self.emit(&DUST, position, 180.0);
+                self.emit(&DUST, position, 0.0);
+            }
+            GameEvent::RolesReversed { .. } => self.hit_stop(0.15),
+            GameEvent::PlayerKilled { position } => {
+                self.emit(&DEBRIS, position, -90.0);
+                self.hit_stop(0.1);
+            }
+            GameEvent::CheckpointReached { position } => {
+                self.emit(&SPARKS, position, -90.0);
+            }
+            _ => (),
+        }
+    }
+
+    /// Advances the effects by real time `dt`, so they keep moving during hit-stop.
+    pub fn update(&mut self, dt: f32) {
+        self.hit_stop = (self.hit_stop - dt).max(0.0);
+
+        self.particles.retain_mut(|particle| {
+            particle.life -= dt;
+            particle.velocity.y += particle.gravity * dt;
+            particle.velocity *= particle.drag.powf(dt);
+            particle.position += particle.velocity * dt;
+            particle.life > 0.0
+        });
+    }
+
+    /// Whether the simulation should skip this frame.
+    pub fn frozen(&self) -> bool {
+        self.hit_stop > 0.0
+    }
+
+    pub(crate) fn draw(&self, d: &mut impl RaylibDraw) {
+        for particle in &self.particles {
+            let alpha = (particle.life / particle.max_life).clamp(0.0, 1.0);
+            d.draw_rectangle_v(
+                rvec2(particle.position.x.floor(), particle.position.y.floor()),
+                rvec2(particle.size, particle.size),
+                particle.color.fade(alpha),
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use crate::ecs::Owner;
+use crate::resources::*;
+use crate::score::ScoreKind;
+
+#[derive(Clone, Copy, Debug)]
+pub enum GameEvent {
+    BulletFired {
+        owner: Owner,
+        position: Vector2,
+        velocity: Vector2,
+    },
+    TowerHit {
+        position: Vector2,
+        reversed: bool,
+    },
+    ShieldHit {
+        position: Vector2,
+    },
+    RolesReversed {
+        flip: bool,
+        position: Vector2,
+    },
+    RolesRestored,
+    BulletsAnnihilated {
+        position: Vector2,
+    },
+    PlayerJumped {
+        position: Vector2,
+    },
+    PlayerLanded {
+        position: Vector2,
+        speed: f32,
+    },
+    /// The player fell out of the level or into a kill zone.
+    PlayerKilled {
+        position: Vector2,
+    },
+    CheckpointReached {
+        position: Vector2,
+    },
+    /// Points earned, before the combo multiplier.
+    Scored {
+        kind: ScoreKind,
+        points: u64,
+        position: Vector2,
+    },
+    GameOver,
+}
+
+impl GameEvent {
+    /// Where in the world the event happened, if anywhere.
+    pub fn position(&self) -> Option<Vector2> {
+        match *self {
+            Self::BulletFired { position, .. }
+            | Self::TowerHit { position, .. }
+            | Self::ShieldHit { position }
+            | Self::RolesReversed { position, .. }
+            | Self::BulletsAnnihilated { position }
+            | Self::PlayerJumped { position }
+            | Self::PlayerLanded { position, .. }
+            | Self::PlayerKilled { position }
+            | Self::CheckpointReached { position }
+            | Self::Scored { position, .. } => Some(position),
+            Self::RolesRestored | Self::GameOver => None,
+        }
+    }
+}
+
+/// Gameplay events produced by the simulation during a frame, drained once per frame by
+/// whoever reacts to them (audio, effects, statistics).
+#[derive(Default)]
+pub struct Events {
+    queue: Vec<GameEvent>,
+}
+
+impl Events {
+    pub fn push(&mut self, event: GameEvent) {
+        self.queue.push(event);
+    }
+
+    pub fn drain(&mut self) -> std::vec::Drain<'_, GameEvent> {
+        self.queue.drain(..)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, GameEvent> {
+        self.queue.iter()
+    }
+}
@@ -0,0 +1,232 @@
+use crate::assets::*;
+use crate::difficulty::*;
+use crate::ecs::*;
+use crate::events::*;
+use crate::manifest::*;
+use crate::player::*;
+use crate::resources::*;
+use crate::scene::*;
+use crate::score::*;
+use crate::stats::*;
+
+/// Time step of headless runs and the validator's jump simulation.
+pub const DT: f32 = 1.0 / 60.0;
+/// Screen pixels per world pixel at the default window size, used by headless runs.
+pub const SCALE: f32 = 4.0;
+/// Seconds after which a headless run is stopped and counted as survived.
+pub const MAX_TIME: f32 = 600.0;
+
+/// One step of a run: the input and how far and at what scale to advance.
+#[derive(Clone, Copy, Debug)]
+pub struct GameStep {
+    pub input: PlayerInput,
+    pub dt: f32,
+    pub scale: f32,
+}
+
+impl GameStep {
+    /// A step at the fixed rate of headless runs.
+    pub fn new(input: PlayerInput) -> Self {
+        Self {
+            input,
+            dt: DT,
+            scale: SCALE,
+        }
+    }
+}
+
+/// One run of a level: the scene, the player and the timers that spawn bullets and reverse
+/// roles. Shared by the windowed game and headless runs.
+pub struct Game {
+    pub scene: Scene,
+    pub player: Player,
+    pub score: Score,
+    pub stats: Stats,
+    lanes: Vec<LaneInfo>,
+    tuning: Tuning,
+    adaptive: Option<Adaptive>,
+    bullet_timer: f32,
+    roles_reversed_timer: f32,
+    play_time: f32,
+}
+
+impl Game {
+    /// Starts a run with `tuning`, adjusted on the fly if `adaptive` is set. The same `seed`
+    /// and inputs always play out the same way.
+    pub fn new(
+        assets: &Assets,
+        level: &LevelInfo,
+        tuning: Tuning,
+        adaptive: bool,
+        seed: u64,
+    ) -> Self {
+        let mut scene = Scene::new(assets, level, seed);
+        scene.tower_mut().tune(&tuning);
+        let roles_reversed_timer = range(&mut scene.rng, tuning.reversal_countdown);
+        Self {
+            scene,
+            player: Player::new(assets, level.spawn()),
+            score: Score::new(),
+            stats: Stats::new(),
+            lanes: level.lanes.clone(),
+            tuning,
+            adaptive: adaptive.then(Adaptive::new),
+            bullet_timer: 0.0,
+            roles_reversed_timer,
+            play_time: 0.0,
+        }
+    }
+
+    /// Picks up reloaded assets and level changes without restarting.
+    pub fn reload(&mut self, assets: &Assets, level: &LevelInfo) {
+        self.lanes = level.lanes.clone();
+        self.scene.reload(assets, level);
+        self.player.reload(assets, &self.scene, level.spawn());
+    }
+
+    /// Advances the run by `dt`. Events are left in `scene.events` for the caller.
+    pub(crate) fn update(&mut self, input: &PlayerInput, dt: f32, scale: f32) {
+        self.player.update(input, dt, scale, &mut self.scene);
+        self.scene.update(dt);
+        self.play_time += dt;
+        for event in self.scene.events.iter() {
+            self.score.handle(event);
+            self.stats.handle(event, self.scene.tower());
+        }
+        self.score.update(dt);
+        self.stats.update(dt, self.scene.tower());
+        if let Some(adaptive) = &mut self.adaptive {
+            for event in self.scene.events.iter() {
+                adaptive.handle(event);
+            }
+            adaptive.update(dt);
+        }
+        let difficulty_scale = self.difficulty_scale();
+
+        if !self.scene.tower().reversed() {
+            // * Bullet timer
+            self.bullet_timer -= dt;
+            while self.bullet_timer <= 0.0 {
+                let [min, max] = self.tuning.bullet_speed;
+                spawn_bullet(
+                    &mut self.scene,
+                    &self.lanes,
+                    [min * difficulty_scale, max * difficulty_scale],
+                );
+                self.bullet_timer += (range(&mut self.scene.rng, self.tuning.bullet_interval)
+                    / difficulty_scale)
+                    .max(MIN_BULLET_INTERVAL);
+            }
+
+            // * Reverse timer
+            self.roles_reversed_timer -= dt;
+            if self.roles_reversed_timer <= 0.0
+                && !self
+                    .player
+                    .rect()
+                    .check_collision_recs(&self.scene.tower_zone())
+            {
+                self.scene.reverse_roles(self.player.center());
+                self.roles_reversed_timer =
+                    range(&mut self.scene.rng, self.tuning.reversal_countdown);
+            }
+        }
+    }
+
+    /// Seconds survived so far.
+    pub fn play_time(&self) -> f32 {
+        self.play_time
+    }
+
+    pub fn bullet_timer(&self) -> f32 {
+        self.bullet_timer
+    }
+
+    pub fn roles_reversed_timer(&self) -> f32 {
+        self.roles_reversed_timer
+    }
+
+    /// How much faster bullets spawn and fly than the tuning says, from adaptive difficulty.
+    pub fn difficulty_scale(&self) -> f32 {
+        self.adaptive.as_ref().map_or(1.0, Adaptive::scale)
+    }
+}
+
+/// A random value in a `[min, max]` range from [`Tuning`].
+fn range(rng: &mut Rng, [min, max]: [f32; 2]) -> f32 {
+    rng.float(min, max)
+}
+
+/// Spawns a bullet flying in from a random lane of the level.
+fn spawn_bullet(scene: &mut Scene, lanes: &[LaneInfo], speed: [f32; 2]) {
+    let lane = if lanes.is_empty() {
+        LaneInfo {
+            side: if scene.rng.int(0, 1) != 0 {
+                Side::Left
+            } else {
+                Side::Right
+            },
+            top: 32.0,
+            bottom: (scene.height() - 24) as f32,
+        }
+    } else {
+        lanes[scene.rng.int(0, lanes.len() as i32 - 1) as usize]
+    };
+    let flip = lane.side == Side::Left;
+    let y = scene.rng.int(lane.top as i32, lane.bottom as i32);
+    let speed = range(&mut scene.rng, speed);
+    let drift = scene.rng.int(-10, 10);
+    scene.spawn_bullet(
+        rvec2(if flip { 0 } else { scene.width() }, y),
+        rvec2(if flip { 1.0 } else { -1.0 } * speed, drift as f32),
+        Owner::Spawner,
+    );
+}
+
+/// Plays `level` from `seed` headless until game over or [`MAX_TIME`], taking each step from
+/// `control` and passing every event to `on_event`. Returns the seconds survived.
+pub fn play_headless(
+    assets: &Assets,
+    level: &LevelInfo,
+    tuning: Tuning,
+    adaptive: bool,
+    seed: u64,
+    mut control: impl FnMut(&Game) -> GameStep,
+    mut on_event: impl FnMut(&GameEvent),
+) -> f32 {
+    let mut game = Game::new(assets, level, tuning, adaptive, seed);
+    while game.play_time() < MAX_TIME {
+        let step = control(&game);
+        game.update(&step.input, step.dt, step.scale);
+        let mut over = false;
+        for event in game.scene.events.drain() {
+            on_event(&event);
+            over |= matches!(event, GameEvent::GameOver);
+        }
+        if over {
+            break;
+        }
+    }
+    game.play_time()
+}
+
+/// Loads the assets for a headless run without opening a window and picks the level named
+/// `name`, or every level.
+pub fn load_headless(name: Option<&str>) -> Option<(Assets, Vec<LevelInfo>)> {
+    set_trace_log(TraceLogLevel::LOG_WARNING);
+    let assets = Assets::load_headless();
+    if let Err(errors) = assets.errors() {
+        eprintln!("{}", errors);
+    }
+    let levels = match name {
+        Some(name) => match assets.manifest().level(name) {
+            Some(level) => vec![level.clone()],
+            None => {
+                eprintln!("No level named {}", name);
+                return None;
+            }
+        },
+        None => assets.manifest().levels.clone(),
+    };
+    Some((assets, levels))
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use crate::assets::*;
+use crate::manifest::*;
+
+const POLL_INTERVAL: f32 = 0.5;
+
+/// Debug-build watcher over every file in the manifest, polling modification times.
+/// Does nothing when assets come from a packed archive.
+pub struct HotReload {
+    dir: Option<PathBuf>,
+    files: HashMap<String, Option<SystemTime>>,
+    timer: f32,
+}
+
+impl HotReload {
+    pub fn new(assets: &Assets) -> Self {
+        let mut hot_reload = Self {
+            dir: match assets.source() {
+                AssetSource::Directory(dir) => Some(dir.clone()),
+                AssetSource::Archive(_) => None,
+            },
+            files: HashMap::new(),
+            timer: POLL_INTERVAL,
+        };
+        hot_reload.watch(assets.manifest());
+        hot_reload
+    }
+
+    /// Starts watching exactly the files in `manifest`, e.g. after it was reloaded.
+    pub fn watch(&mut self, manifest: &Manifest) {
+        let files = std::iter::once(MANIFEST_PATH.to_owned()).chain(manifest.files());
+        self.files = files
+            .map(|file| {
+                let modified = self.modified(&file);
+                (file, modified)
+            })
+            .collect();
+    }
+
+    fn modified(&self, file: &str) -> Option<SystemTime> {
+        let dir = self.dir.as_ref()?;
+        std::fs::metadata(dir.join(file))
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    }
+
+    /// Files changed since the last poll.
+    pub fn poll(&mut self, dt: f32) -> Vec<String> {
+        self.timer -= dt;
+        if self.dir.is_none() || self.timer > 0.0 {
+            return Vec::new();
+        }
+        self.timer = POLL_INTERVAL;
+
+        let mut changed = Vec::new();
+        for (file, modified) in &self.files {
+            let current = self.modified(file);
+            if current != *modified {
+                changed.push(file.clone());
+            }
+        }
+        for file in &changed {
+            let modified = self.modified(file);
+            self.files.insert(file.clone(), modified);
+        }
+        changed
+    }
+}
@@ -0,0 +1,195 @@
+use crate::events::*;
+use crate::indicators;
+use crate::resources::*;
+use crate::scene::*;
+use crate::viewport::*;
+
+/// Window height the HUD is laid out for; everything scales with the actual height.
+const BASE_HEIGHT: f32 = 480.0;
+/// Seconds the "Roles Reversed!" banner stays up.
+const BANNER_TIME: f32 = 2.0;
+/// Seconds an achievement toast stays up.
+const TOAST_TIME: f32 = 4.0;
+
+/// Screen-space overlay drawn on top of the world, independent of the camera.
+pub struct Hud {
+    pub show_fps: bool,
+    pub score: u64,
+    pub combo: u32,
+    pub multiplier: u32,
+    banner: Option<f32>,
+    /// Achievement toasts with the seconds they have left.
+    toasts: Vec<(String, f32)>,
+}
+
+impl Hud {
+    pub fn new(show_fps: bool) -> Self {
+        Self {
+            show_fps,
+            score: 0,
+            combo: 0,
+            multiplier: 1,
+            banner: None,
+            toasts: Vec::new(),
+        }
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        if let GameEvent::RolesReversed { .. } = event {
+            self.banner = Some(BANNER_TIME);
+        }
+    }
+
+    pub fn toast(&mut self, text: String) {
+        self.toasts.push((text, TOAST_TIME));
+    }
+
+    /// Returns whether the FPS display was toggled, so the caller can persist it.
+    pub fn update(&mut self, rl: &RaylibHandle) -> bool {
+        for (_, timer) in &mut self.toasts {
+            *timer -= rl.get_frame_time();
+        }
+        self.toasts.retain(|(_, timer)| *timer > 0.0);
+
+        if let Some(timer) = self.banner.as_mut() {
+            *timer -= rl.get_frame_time();
+            if *timer <= 0.0 {
+                self.banner = None;
+            }
+        }
+
+        if rl.is_key_pressed(KeyboardKey::KEY_F1) {
+            self.show_fps = !self.show_fps;
+            return true;
+        }
+        false
+    }
+
+    pub fn draw(
+        &self,
+        d: &mut RaylibDrawHandle,
+        scene: &Scene,
+        viewport: &Viewport,
+        camera: Camera2D,
+        play_time: f32,
+    ) {
+        let screen = rvec2(d.get_screen_width(), d.get_screen_height());
+        let scale = screen.y / BASE_HEIGHT;
+        let margin = 12.0 * scale;
+        let font = |size: f32| (size * scale) as i32;
+
+        indicators::draw(d, viewport, camera, &indicators::incoming(scene));
+
+        // * Tower health
+        let bar_size = rvec2(200, 14) * scale;
+        let mut position = rvec2(margin, margin);
+        draw_bar(d, position, bar_size, scene.tower().health(), Color::RED);
+        d.draw_text(
+            "Tower",
+            (position.x + bar_size.x + margin / 2.0) as i32,
+            position.y as i32,
+            font(14.0),
+            Color::RAYWHITE,
+        );
+
+        if let (Some(bad_health), Some(timer)) =
+            (scene.tower().bad_health(), scene.tower().reversal_timer())
+        {
+            position.y += bar_size.y + margin / 2.0;
+            draw_bar(d, position, bar_size, bad_health, Color::BLUE);
+            let color = if timer < 3.0 && (timer * 4.0) as i32 % 2 == 0 {
+                Color::RED
+            } else {
+                Color::RAYWHITE
+            };
+            d.draw_text(
+                &format!("{:.1}s", timer.max(0.0)),
+                (position.x + bar_size.x + margin / 2.0) as i32,
+                position.y as i32,
+                font(14.0),
+                color,
+            );
+        }
+
+        // * Score
+        let right = |text: &str, size: i32| (screen.x - margin) as i32 - measure_text(text, size);
+        let score = format!("{}", self.score);
+        d.draw_text(
+            &score,
+            right(&score, font(30.0)),
+            margin as i32,
+            font(30.0),
+            Color::RAYWHITE,
+        );
+        let mut y = margin + 32.0 * scale;
+        if self.combo >= 2 {
+            let combo = format!("{} combo x{}", self.combo, self.multiplier);
+            d.draw_text(
+                &combo,
+                right(&combo, font(16.0)),
+                y as i32,
+                font(16.0),
+                Color::GOLD,
+            );
+            y += 18.0 * scale;
+        }
+        let time = format!("{}:{:02}", play_time as i32 / 60, play_time as i32 % 60);
+        d.draw_text(
+            &time,
+            right(&time, font(16.0)),
+            y as i32,
+            font(16.0),
+            Color::RAYWHITE,
+        );
+
+        // * Banner
+        if let Some(timer) = self.banner {
+            let size = font(40.0 + 20.0 * timer / BANNER_TIME);
+            let text = "Roles Reversed!";
+            let alpha = (timer / BANNER_TIME * 2.0).min(1.0);
+            d.draw_text(
+                text,
+                (screen.x as i32 - measure_text(text, size)) / 2,
+                (screen.y / 4.0) as i32,
+                size,
+                Color::RAYWHITE.fade(alpha),
+            );
+        }
+
+        // * Toasts, newest at the bottom
+        let mut y = screen.y - margin;
+        for (text, timer) in self.toasts.iter().rev() {
+            let size = font(20.0);
+            y -= size as f32 + margin / 2.0;
+            let width = measure_text(text, size) as f32 + margin;
+            let x = (screen.x - width) / 2.0;
+            let alpha = timer.min(1.0);
+            d.draw_rectangle_v(
+                rvec2(x, y - margin / 4.0),
+                rvec2(width, size as f32 + margin / 2.0),
+                Color::BLACK.fade(0.6 * alpha),
+            );
+            d.draw_text(
+                text,
+                (x + margin / 2.0) as i32,
+                y as i32,
+                size,
+                Color::GOLD.fade(alpha),
+            );
+        }
+
+        if self.show_fps {
+            d.draw_fps(margin as i32, (screen.y - margin) as i32 - font(20.0));
+        }
+    }
+}
+
+fn draw_bar(d: &mut RaylibDrawHandle, position: Vector2, size: Vector2, value: f32, color: Color) {
+    d.draw_rectangle_v(position, size, Color::BLACK);
+    d.draw_rectangle_v(position + 2.0, size - 4.0, Color::GRAY);
+    d.draw_rectangle_v(
+        position + 2.0,
+        (size - 4.0) * rvec2(value.clamp(0.0, 1.0), 1),
+        color,
+    );
+}
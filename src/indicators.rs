@@ -0,0 +1,84 @@
+use crate::ecs::*;
+use crate::resources::*;
+use crate::scene::*;
+use crate::viewport::*;
+
+/// Distance of the indicators from the screen edge, in pixels.
+const MARGIN: f32 = 24.0;
+/// Threats closer than this many seconds to the tower are drawn in red.
+const URGENT: f32 = 1.5;
+
+#[derive(Clone, Copy, Debug)]
+pub struct Threat {
+    pub position: Vector2,
+    pub distance: f32,
+    pub time_to_impact: f32,
+}
+
+/// Bullets whose straight-line trajectory ends in the tower, ignoring ricochets.
+pub fn incoming(scene: &Scene) -> Vec<Threat> {
+    let tower = scene.tower().rect();
+    scene
+        .bullets()
+        .filter_map(|bullet| {
+            let position = scene.world.center(bullet)?;
+            let Velocity(velocity) = *scene.world.velocities.get(bullet)?;
+
+            let time = if velocity.x > 0.0 && position.x < tower.x {
+                (tower.x - position.x) / velocity.x
+            } else if velocity.x < 0.0 && position.x > tower.x + tower.width {
+                (tower.x + tower.width - position.x) / velocity.x
+            } else {
+                return None;
+            };
+
+            let y = position.y + velocity.y * time;
+            if y < tower.y || y > tower.y + tower.height {
+                return None;
+            }
+            Some(Threat {
+                position,
+                distance: velocity.length() * time,
+                time_to_impact: time,
+            })
+        })
+        .collect()
+}
+
+/// Edge arrows pointing at threats that are outside of the camera view.
+pub fn draw(d: &mut RaylibDrawHandle, viewport: &Viewport, camera: Camera2D, threats: &[Threat]) {
+    let view = viewport.rect();
+    let center = rvec2(view.x + view.width / 2.0, view.y + view.height / 2.0);
+
+    for threat in threats {
+        let on_screen = viewport.world_to_screen(threat.position, camera);
+        if view.check_collision_point_rec(on_screen) {
+            continue;
+        }
+
+        let direction = on_screen - center;
+        let scale = ((view.width / 2.0 - MARGIN) / direction.x.abs())
+            .min((view.height / 2.0 - MARGIN) / direction.y.abs());
+        let arrow = center + direction * scale;
+
+        let color = if threat.time_to_impact < URGENT {
+            Color::RED
+        } else {
+            Color::ORANGE
+        };
+        d.draw_poly(
+            arrow,
+            3,
+            10.0,
+            // DrawPoly puts the first vertex at (0, radius) before rotating, which turns
+            // it to (-sin, cos) of the angle.
+            (-direction.x).atan2(direction.y).to_degrees(),
+            color,
+        );
+
+        let text = format!("{:.0} {:.1}s", threat.distance, threat.time_to_impact);
+        let width = measure_text(&text, 10) as f32;
+        let label = arrow - direction.normalized() * 18.0 - rvec2(width / 2.0, 5.0);
+        d.draw_text(&text, label.x as i32, label.y as i32, 10, color);
+    }
+}
@@ -1,119 +1,423 @@
+pub mod achievements;
+pub mod archive;
+pub mod assets;
+pub mod audio;
+pub mod bot;
+pub mod camera;
+pub mod debug;
+pub mod difficulty;
+pub mod ecs;
+pub mod editor;
+pub mod effects;
+pub mod events;
+pub mod game;
+#[cfg(debug_assertions)]
+pub mod hot_reload;
+pub mod hud;
+pub mod indicators;
+pub mod manifest;
+pub mod mask;
+pub mod parallax;
 pub mod player;
 pub mod resources;
 pub mod scene;
+pub mod score;
+pub mod settings;
+pub mod simulate;
+pub mod stats;
 pub mod tower;
+pub mod validate;
+pub mod viewport;
 
+use std::io::Write;
+
+use achievements::*;
+use assets::*;
+use audio::*;
+use bot::*;
+use camera::*;
+use debug::*;
+use ecs::*;
+use editor::*;
+use effects::*;
+use events::*;
+use game::*;
+use hud::*;
 use player::*;
 use resources::*;
-use scene::*;
+use settings::*;
+use stats::*;
+use viewport::*;
 
 fn main() {
+    let args = std::env::args().collect::<Vec<_>>();
+    if args.get(1).map(String::as_str) == Some("pack") {
+        let output = args
+            .get(2)
+            .map(String::as_str)
+            .unwrap_or(archive::ARCHIVE_PATH);
+        match archive::pack(archive::ASSETS_DIR, output) {
+            Ok(count) => println!("Packed {} files into {}", count, output),
+            Err(err) => {
+                eprintln!("Failed to pack assets: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("bot") {
+        if !bot::run(&args[2..]) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("simulate") {
+        if !simulate::run(&args[2..]) {
+            std::process::exit(1);
+        }
+        return;
+    }
+    if args.get(1).map(String::as_str) == Some("validate") {
+        if !validate::run(args.get(2).map(String::as_str)) {
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Every step of player input is written here for `simulate --inputs` to replay.
+    let mut recording = match (args.get(1).map(String::as_str), args.get(2)) {
+        (Some("record"), Some(path)) => match std::fs::File::create(path) {
+            Ok(file) => Some(std::io::BufWriter::new(file)),
+            Err(err) => {
+                eprintln!("Failed to create {}: {}", path, err);
+                std::process::exit(1);
+            }
+        },
+        _ => None,
+    };
+    // Seed the recorded game starts from, so `simulate --seeds` can replay it.
+    let record_seed = args.get(3).and_then(|seed| seed.parse().ok()).unwrap_or(1);
+
     let (mut rl, thread) = raylib::init()
         .size(860, 480)
         .resizable()
         .title("Personality")
         .build();
 
-    let mut audio = RaylibAudio::init_audio_device();
+    let device = RaylibAudio::init_audio_device();
 
-    let bullet_texture = rl
-        .load_texture(&thread, "Assets/Bullet.png")
-        .expect("Failed to load bullet texture.");
+    let assets = Assets::load(&mut rl, &thread);
+    if let Err(errors) = assets.errors() {
+        eprintln!("{}", errors);
+    }
+    let mut settings = Settings::load();
+    let mut achievements = Achievements::load();
+    let mut viewport = Viewport::new(settings.scaling);
+    set_fullscreen(&mut rl, settings.fullscreen);
+    let mut audio = Audio::new(device, &thread, &assets, settings.audio);
+    let mut level = match assets.manifest().levels.first() {
+        Some(level) => level.clone(),
+        None => {
+            eprintln!("No levels found in the asset manifest.");
+            return;
+        }
+    };
 
-    let game_over_sound =
-        Sound::load_sound("Assets/GameOver.wav").expect("Failed to load game over sound.");
+    #[cfg(debug_assertions)]
+    let mut assets = assets;
+    #[cfg(debug_assertions)]
+    let mut hot_reload = hot_reload::HotReload::new(&assets);
+    let mut debug = DebugTools::new();
+    let mut editor = Editor::new();
+    // Lets a bot play instead of the keyboard and mouse.
+    let mut autoplay: Option<Bot> = None;
+    // Collision mask painted in the editor, kept across test-plays until it is saved.
+    let mut mask: Option<Image> = None;
 
     loop {
-        let mut player = Player::new(&mut rl, &thread, rvec2(10, 40));
-        let mut scene = Scene::new(&mut rl, &thread, 1);
+        let seed = if recording.is_some() {
+            record_seed
+        } else {
+            clock_seed()
+        };
+        let mut game = Game::new(
+            &assets,
+            &level,
+            settings.difficulty.tuning(&settings.custom_difficulty),
+            settings.adaptive,
+            seed,
+        );
+        if let Some(mask) = &mask {
+            game.scene.set_metadata_image(mask);
+        }
+        achievements.start_run();
+        let mut camera = CameraController::new(game.player.center());
 
-        let mut bullet_timer = 0.0;
-        let mut roles_reversed_timer = get_random_value::<i32>(10, 20) as f32;
-        let mut roles_reversed_text_timer = None;
-        let mut play_time = 0.0;
+        let mut hud = Hud::new(settings.show_fps);
+        let mut effects = Effects::new();
         let mut game_over = false;
-        while !game_over {
+        let mut restart = false;
+        while !game_over && !restart {
             if rl.window_should_close() {
+                save_achievements(&achievements);
                 return;
             }
 
-            player.update(&rl, &mut audio, &mut scene);
-            scene.update(&mut rl, &mut audio, &bullet_texture, &mut game_over);
-            play_time += rl.get_frame_time();
-
-            if !scene.tower().reversed() {
-                // * Bullet timer
-                bullet_timer -= rl.get_frame_time();
-                while bullet_timer <= 0.0 {
-                    let flip = get_random_value::<i32>(0, 1) != 0;
-                    scene.bullets.push(Bullet::new(
-                        rvec2(
-                            if flip { 0 } else { scene.width() },
-                            get_random_value::<i32>(32, scene.height() - 24),
-                        ),
-                        rvec2(
-                            if flip { 1 } else { -1 } * get_random_value::<i32>(10, 200),
-                            get_random_value::<i32>(-10, 10),
-                        ),
-                    ));
-                    bullet_timer += get_random_value::<i32>(100, 200) as f32 / 100.0;
+            #[cfg(debug_assertions)]
+            {
+                let changed = hot_reload.poll(rl.get_frame_time());
+                if !changed.is_empty() {
+                    for error in assets.reload(&mut rl, &thread, &changed) {
+                        eprintln!("Failed to reload {}: {}", error.path, error.reason);
+                    }
+                    hot_reload.watch(assets.manifest());
+                    if let Some(reloaded) = assets.manifest().level(&level.name) {
+                        level = reloaded.clone();
+                    }
+                    audio.reload(&assets);
+                    let music = &assets.manifest().music;
+                    if changed
+                        .iter()
+                        .any(|file| music.values().any(|path| path == file))
+                    {
+                        audio.reload_music(&thread, &assets);
+                    }
+                    game.reload(&assets, &level);
                 }
+            }
 
-                // * Reverse timer
-                roles_reversed_timer -= rl.get_frame_time();
-                if roles_reversed_timer <= 0.0
-                    && !player.rect().check_collision_recs(&{
-                        let mut rect = scene.tower().rect();
-                        rect.x -= 10.0;
-                        rect.width += 20.0;
-                        rect.y = 0.0;
-                        rect.height = scene.height() as _;
-                        rect
-                    })
-                {
-                    scene.reverse_roles(player.center(), &mut audio);
-                    roles_reversed_timer = get_random_value::<i32>(10, 20) as f32;
-                    roles_reversed_text_timer = Some(1.0);
+            // * Viewport
+            if viewport.controls(&mut rl) {
+                settings.scaling = viewport.scaling();
+                settings.fullscreen = rl.is_window_fullscreen();
+                save_settings(&settings);
+            }
+            viewport.update(&mut rl, &thread, game.scene.size());
+
+            // * Debug
+            let command = debug.update(&rl);
+            if command.is_some() {
+                stop_recording(&mut recording, "console commands can't be replayed");
+            }
+            match command {
+                Some(Command::Spawn { position, velocity }) => {
+                    game.scene.spawn_bullet(position, velocity, Owner::Spawner);
                 }
+                Some(Command::Reverse) => game.scene.reverse_roles(game.player.center()),
+                Some(Command::TowerHealth(health)) => game.scene.tower_mut().set_health(health),
+                Some(Command::Teleport(position)) => game.player.teleport(position),
+                None => (),
+            }
+
+            if rl.is_key_pressed(KeyboardKey::KEY_F5) && !debug.console_open() {
+                autoplay = match autoplay {
+                    Some(_) => None,
+                    None => Some(Bot::new(Strategy::Balanced)),
+                };
             }
 
-            if let Some(timer) = roles_reversed_text_timer.as_mut() {
-                *timer -= rl.get_frame_time() / 2.0;
-                if *timer <= 0.0 {
-                    roles_reversed_text_timer = None;
+            // * Editor
+            if rl.is_key_pressed(KeyboardKey::KEY_F4) && !debug.console_open() {
+                if let Some(edited) = editor.close() {
+                    level = edited;
+                    mask = Some(game.scene.metadata_image());
+                    restart = true;
+                } else {
+                    stop_recording(&mut recording, "editing can't be replayed");
+                    editor.open(&level, camera.camera().target);
                 }
             }
+            if !debug.console_open() {
+                editor.update(
+                    &rl,
+                    &assets,
+                    &viewport,
+                    camera.camera(),
+                    &mut game.scene,
+                    game.player.size(),
+                );
+            }
 
-            // * Draw
-            let mut d = rl.begin_drawing(&thread);
-            d.clear_background(Color::SKYBLUE);
-            {
-                let mut d = d.begin_mode2D(player.camera());
-                scene.draw(&mut d, &bullet_texture);
-                player.draw(&mut d);
+            // * Simulation, paused during hit-stop, while typing in the console and while
+            // editing
+            let dt = rl.get_frame_time() * debug.time_scale();
+            if !effects.frozen() && !debug.console_open() && !editor.active() {
+                let input = match &mut autoplay {
+                    Some(bot) => {
+                        game.stats.autoplayed = true;
+                        bot.input(dt, &game.scene, &game.player)
+                    }
+                    None => PlayerInput::read(&rl, &viewport, camera.camera()),
+                };
+                let step = GameStep {
+                    input,
+                    dt,
+                    scale: viewport.scale(),
+                };
+                if let Some(file) = &mut recording {
+                    if let Err(err) = simulate::record(file, step) {
+                        eprintln!("Failed to record input: {}", err);
+                        recording = None;
+                    }
+                }
+                game.update(&step.input, step.dt, step.scale);
             }
-            d.draw_fps(12, 12);
-            if let Some(timer) = roles_reversed_text_timer {
-                d.draw_text(
-                    "Roles Reversed!",
-                    20,
-                    20,
-                    (timer * 100.0) as _,
-                    Color::RAYWHITE,
+
+            // * Events
+            for event in game.scene.events.drain() {
+                audio.handle(&event);
+                effects.handle(&event);
+                camera.handle(&event);
+                hud.handle(&event);
+                if autoplay.is_none() {
+                    achievements.handle(&event);
+                }
+                if let GameEvent::GameOver = event {
+                    game_over = true;
+                }
+            }
+
+            // * Achievements, which the bot doesn't earn. Goals for a single run stay off for
+            // the rest of a run the bot played part of.
+            if autoplay.is_none() {
+                let unlocked = achievements.update(&assets.manifest().achievements, &game);
+                for info in &unlocked {
+                    hud.toast(format!("Achievement unlocked: {}", info.name));
+                }
+                if !unlocked.is_empty() {
+                    save_achievements(&achievements);
+                }
+            }
+
+            effects.update(rl.get_frame_time());
+            if editor.active() {
+                let view = editor.view();
+                camera.update(
+                    rl.get_frame_time(),
+                    &viewport,
+                    game.scene.size(),
+                    view,
+                    view,
+                );
+            } else {
+                camera.update(
+                    rl.get_frame_time(),
+                    &viewport,
+                    game.scene.size(),
+                    game.player.center(),
+                    viewport.screen_to_world(rl.get_mouse_position(), camera.camera()),
                 );
             }
+
+            // * HUD
+            hud.score = game.score.total();
+            hud.combo = game.score.combo();
+            hud.multiplier = game.score.multiplier();
+            if hud.update(&rl) {
+                settings.show_fps = hud.show_fps;
+                save_settings(&settings);
+            }
+
+            // * Audio
+            let camera = camera.camera();
+            audio.set_listener(camera.target, camera.offset.x / camera.zoom);
+            audio.update(rl.get_frame_time(), game.scene.tower().reversed());
+            if !debug.console_open() && audio.volume_controls(&rl) {
+                settings.audio = audio.settings();
+                save_settings(&settings);
+            }
+
+            // * Draw
+            let mouse = viewport.screen_to_world(rl.get_mouse_position(), camera);
+            let mut d = rl.begin_drawing(&thread);
+            if let Some(target) = viewport.target() {
+                let mut d = d.begin_texture_mode(&thread, target);
+                d.clear_background(Color::SKYBLUE);
+                let mut d = d.begin_mode2D(camera);
+                game.scene.draw_background(&mut d, camera);
+                game.scene.draw(&mut d);
+                game.player.draw(&mut d);
+                effects.draw(&mut d);
+                game.scene.draw_foreground(&mut d, camera);
+                debug.draw_world(&mut d, &game.scene, &game.player);
+                editor.draw_world(&mut d, &game.scene, game.player.size(), mouse);
+            }
+            d.clear_background(Color::BLACK);
+            viewport.draw(&mut d);
+            hud.draw(&mut d, &game.scene, &viewport, camera, game.play_time());
+            editor.draw(&mut d);
+            debug.draw(
+                &mut d,
+                &[
+                    ("bullet timer", game.bullet_timer()),
+                    ("reverse timer", game.roles_reversed_timer()),
+                    ("difficulty scale", game.difficulty_scale()),
+                    (
+                        "reversal timer",
+                        game.scene.tower().reversal_timer().unwrap_or_default(),
+                    ),
+                    ("tower health", game.scene.tower().health()),
+                    (
+                        "bad health",
+                        game.scene.tower().bad_health().unwrap_or_default(),
+                    ),
+                ],
+            );
         }
 
-        audio.play_sound(&game_over_sound);
+        // A recording covers one run from the recorded seed
+        stop_recording(&mut recording, "the run ended");
+
+        // * Stats
+        let lifetime = if game_over {
+            game.stats.finish(
+                &level.name,
+                settings.difficulty,
+                settings.adaptive,
+                &game.score,
+            );
+            if let Err(err) = game.stats.append() {
+                eprintln!("Failed to write stats: {}", err);
+            }
+            save_achievements(&achievements);
+            Lifetime::load()
+        } else {
+            Lifetime::default()
+        };
+        let mut show_lifetime = false;
 
         while game_over {
             if rl.window_should_close() {
+                save_achievements(&achievements);
                 return;
             }
 
             if rl.is_key_pressed(KeyboardKey::KEY_P) {
                 game_over = false;
             }
+            if rl.is_key_pressed(KeyboardKey::KEY_S) {
+                show_lifetime = !show_lifetime;
+            }
+            // Keys gameplay doesn't use, so ones still held from the run change nothing
+            if rl.is_key_pressed(KeyboardKey::KEY_C) {
+                settings.difficulty = settings.difficulty.next();
+                save_settings(&settings);
+            }
+            if rl.is_key_pressed(KeyboardKey::KEY_V) {
+                settings.adaptive = !settings.adaptive;
+                save_settings(&settings);
+            }
+
+            audio.update(rl.get_frame_time(), false);
+            if audio.volume_controls(&rl) {
+                settings.audio = audio.settings();
+                save_settings(&settings);
+            }
+            if viewport.controls(&mut rl) {
+                settings.scaling = viewport.scaling();
+                settings.fullscreen = rl.is_window_fullscreen();
+                save_settings(&settings);
+            }
 
             let screen_size = (rl.get_screen_width(), rl.get_screen_height());
             let mut d = rl.begin_drawing(&thread);
@@ -124,7 +428,7 @@ fn main() {
                 60,
                 Color::WHITE,
             );
-            let score = &format!("You held on for {} seconds", play_time as i32);
+            let score = &format!("You held on for {} seconds", game.play_time() as i32);
             d.draw_text(
                 score,
                 (screen_size.0 - measure_text(score, 30)) / 2,
@@ -132,13 +436,86 @@ fn main() {
                 30,
                 Color::WHITE,
             );
+            let restart = "Press P to restart, S for lifetime stats";
             d.draw_text(
-                "Press P to restart",
-                (screen_size.0 - measure_text("Press P to restart", 20)) / 2,
+                restart,
+                (screen_size.0 - measure_text(restart, 20)) / 2,
                 110,
                 20,
                 Color::WHITE,
             );
+            let difficulty = &format!(
+                "Difficulty: {} (C), adaptive: {} (V)",
+                settings.difficulty.name(),
+                if settings.adaptive { "on" } else { "off" }
+            );
+            d.draw_text(
+                difficulty,
+                (screen_size.0 - measure_text(difficulty, 20)) / 2,
+                140,
+                20,
+                Color::GRAY,
+            );
+
+            // * Score breakdown, or lifetime stats
+            let mut y = 180;
+            let lines = if show_lifetime {
+                let infos = &assets.manifest().achievements;
+                let mut lines = lifetime.lines();
+                lines.push(format!(
+                    "Achievements: {} of {}",
+                    infos
+                        .iter()
+                        .filter(|info| achievements.unlocked(&info.id))
+                        .count(),
+                    infos.len()
+                ));
+                lines
+            } else {
+                let mut lines = game
+                    .score
+                    .breakdown()
+                    .map(|(kind, line)| {
+                        format!("{}: {} for {}", kind.name(), line.count, line.points)
+                    })
+                    .collect::<Vec<_>>();
+                lines.push(format!("Best combo: {}", game.score.best_combo()));
+                lines.push(format!("Accuracy: {:.0}%", game.stats.accuracy() * 100.0));
+                lines.push(format!("Score: {}", game.score.total()));
+                lines
+            };
+            for line in &lines {
+                d.draw_text(
+                    line,
+                    (screen_size.0 - measure_text(line, 20)) / 2,
+                    y,
+                    20,
+                    Color::WHITE,
+                );
+                y += 24;
+            }
         }
     }
 }
+
+/// Ends the recording early when the game does something the inputs alone can't replay.
+fn stop_recording(recording: &mut Option<std::io::BufWriter<std::fs::File>>, reason: &str) {
+    if let Some(mut file) = recording.take() {
+        if let Err(err) = file.flush() {
+            eprintln!("Failed to record input: {}", err);
+        }
+        eprintln!("Stopped recording: {}", reason);
+    }
+}
+
+fn save_achievements(achievements: &Achievements) {
+    if let Err(err) = achievements.save() {
+        eprintln!("Failed to save achievements: {}", err);
+    }
+}
+
+fn save_settings(settings: &Settings) {
+    if let Err(err) = settings.save() {
+        eprintln!("Failed to save settings: {}", err);
+    }
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::resources::*;
+
+pub const MANIFEST_PATH: &str = "manifest.json";
+/// The manifest layout this build understands.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Everything the game loads, with paths relative to the asset directory.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub version: u32,
+    #[serde(default)]
+    pub textures: BTreeMap<String, String>,
+    #[serde(default)]
+    pub animations: BTreeMap<String, AnimationInfo>,
+    #[serde(default)]
+    pub images: BTreeMap<String, String>,
+    #[serde(default)]
+    pub sounds: BTreeMap<String, String>,
+    /// Streamed rather than loaded up front.
+    #[serde(default)]
+    pub music: BTreeMap<String, String>,
+    #[serde(default)]
+    pub levels: Vec<LevelInfo>,
+    #[serde(default)]
+    pub achievements: Vec<AchievementInfo>,
+}
+
+/// A sprite sheet and the Aseprite-style JSON atlas describing its frames and clips.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimationInfo {
+    pub sheet: String,
+    pub atlas: String,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LevelInfo {
+    pub name: String,
+    /// Texture drawn as the level background.
+    pub art: String,
+    /// Image whose black pixels are solid.
+    pub metadata: String,
+    pub tower: String,
+    pub shield: String,
+    pub tower_position: [f32; 2],
+    pub spawn: [f32; 2],
+    /// Parallax layers, drawn in order. Purely visual; collision only comes from `metadata`.
+    #[serde(default)]
+    pub layers: Vec<LayerInfo>,
+    /// Where spawned bullets enter the level; both sides at any height when empty.
+    #[serde(default)]
+    pub lanes: Vec<LaneInfo>,
+    /// What lies past each edge of the level.
+    #[serde(default)]
+    pub edges: Edges,
+    /// Points on the ground the player respawns at once they have walked past them.
+    #[serde(default)]
+    pub checkpoints: Vec<[f32; 2]>,
+    /// Areas that kill the player, as x, y, width and height.
+    #[serde(default)]
+    pub kill_zones: Vec<[f32; 4]>,
+    /// Tower health lost each time the player dies.
+    #[serde(default = "default_death_penalty")]
+    pub death_penalty: f32,
+}
+
+/// How the collision mask behaves past one edge of the level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Edge {
+    /// Solid, like a black pixel.
+    Wall,
+    /// Open space.
+    Void,
+    /// Open space that kills the player.
+    Kill,
+    /// Continues from the opposite edge.
+    Wrap,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct Edges {
+    pub left: Edge,
+    pub right: Edge,
+    pub top: Edge,
+    pub bottom: Edge,
+}
+
+impl Default for Edges {
+    fn default() -> Self {
+        Self {
+            left: Edge::Wall,
+            right: Edge::Wall,
+            top: Edge::Void,
+            bottom: Edge::Kill,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Side {
+    Left,
+    Right,
+}
+
+/// A band of heights on one side of the level that spawned bullets fly in from.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct LaneInfo {
+    pub side: Side,
+    pub top: f32,
+    pub bottom: f32,
+}
+
+/// A background or foreground layer that scrolls at its own rate relative to the camera.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LayerInfo {
+    /// Animation drawn for the layer; still art is a one-frame atlas.
+    pub animation: String,
+    /// Clip to loop, if the layer is animated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clip: Option<String>,
+    /// How much the layer follows the camera: 0 stays fixed on screen, 1 moves with the
+    /// level, and anything above 1 passes by faster than the level.
+    #[serde(default = "default_scroll")]
+    pub scroll: [f32; 2],
+    /// Position of the layer when the camera is at the top left of the level.
+    #[serde(default)]
+    pub offset: [f32; 2],
+    /// Constant drift in pixels per second, for things like clouds.
+    #[serde(default)]
+    pub velocity: [f32; 2],
+    /// Tiles the layer horizontally to fill the view.
+    #[serde(default)]
+    pub repeat: bool,
+    /// Drawn in front of the player instead of behind the level art.
+    #[serde(default)]
+    pub foreground: bool,
+}
+
+/// Something to do once for good, checked against gameplay events.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AchievementInfo {
+    /// Key the unlock is saved under, so renaming an achievement keeps it unlocked.
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub goal: Goal,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Goal {
+    /// Something done `count` times in one run, or over every run if `lifetime` is set.
+    Count {
+        counter: Counter,
+        count: u32,
+        #[serde(default)]
+        lifetime: bool,
+    },
+    /// Win a reversal within `seconds` of it starting.
+    QuickReversal {
+        seconds: f32,
+    },
+    /// Survive `seconds` in one run with the tower never below `min_health`.
+    Survive {
+        seconds: f32,
+        #[serde(default)]
+        min_health: f32,
+    },
+    Score {
+        points: u64,
+    },
+    Combo {
+        count: u32,
+    },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Counter {
+    Shots,
+    /// Bullets shot down on their way to the tower.
+    Interceptions,
+    /// Bullets shot down anywhere, interceptions included.
+    Annihilations,
+    TowerShots,
+    Reversals,
+    ReversalsWon,
+    Jumps,
+    Deaths,
+    Checkpoints,
+}
+
+fn default_scroll() -> [f32; 2] {
+    [1.0, 1.0]
+}
+
+fn default_death_penalty() -> f32 {
+    0.05
+}
+
+impl LevelInfo {
+    pub fn tower_position(&self) -> Vector2 {
+        rvec2(self.tower_position[0], self.tower_position[1])
+    }
+
+    pub fn spawn(&self) -> Vector2 {
+        rvec2(self.spawn[0], self.spawn[1])
+    }
+
+    pub fn checkpoints(&self) -> Vec<Vector2> {
+        self.checkpoints.iter().map(|&[x, y]| rvec2(x, y)).collect()
+    }
+
+    pub fn kill_zones(&self) -> Vec<Rectangle> {
+        self.kill_zones
+            .iter()
+            .map(|&[x, y, width, height]| rrect(x, y, width, height))
+            .collect()
+    }
+}
+
+impl Manifest {
+    pub fn parse(bytes: &[u8]) -> Result<Self, String> {
+        let manifest: Self =
+            serde_json::from_slice(bytes).map_err(|err| format!("Invalid manifest: {}", err))?;
+        if manifest.version != MANIFEST_VERSION {
+            return Err(format!(
+                "Unsupported manifest version {} (expected {})",
+                manifest.version, MANIFEST_VERSION
+            ));
+        }
+        Ok(manifest)
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|err| err.to_string())
+    }
+
+    /// Every file referenced by the manifest, each listed once.
+    pub fn files(&self) -> Vec<String> {
+        let mut files = self
+            .textures
+            .values()
+            .chain(
+                self.animations
+                    .values()
+                    .flat_map(|animation| [&animation.sheet, &animation.atlas]),
+            )
+            .chain(self.images.values())
+            .chain(self.sounds.values())
+            .chain(self.music.values())
+            .cloned()
+            .collect::<Vec<_>>();
+        files.sort();
+        files.dedup();
+        files
+    }
+
+    pub fn level(&self, name: &str) -> Option<&LevelInfo> {
+        self.levels.iter().find(|level| level.name == name)
+    }
+}
@@ -0,0 +1,277 @@
+use crate::manifest::*;
+use crate::resources::*;
+
+/// What a level is made of at one pixel.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Sample {
+    Open,
+    Solid,
+    Kill,
+}
+
+/// A level's collision mask, where black pixels are solid. Sampling never goes out of
+/// bounds: positions past an edge follow the level's [`Edges`].
+pub struct Mask {
+    colors: ImageColors,
+    width: i32,
+    height: i32,
+    edges: Edges,
+}
+
+impl Mask {
+    pub fn new(image: &Image, edges: Edges) -> Self {
+        Self {
+            colors: image.get_image_data(),
+            width: image.width(),
+            height: image.height(),
+            edges,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn edges(&self) -> Edges {
+        self.edges
+    }
+
+    /// The pixel at `x`, `y`, or `None` outside of the mask.
+    pub fn get(&self, x: i32, y: i32) -> Option<Color> {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            Some(self.colors[(x + y * self.width) as usize])
+        } else {
+            None
+        }
+    }
+
+    /// Sets one pixel, ignoring positions outside of the mask.
+    pub fn set(&mut self, x: i32, y: i32, color: Color) {
+        if x >= 0 && y >= 0 && x < self.width && y < self.height {
+            self.colors[(x + y * self.width) as usize] = color;
+        }
+    }
+
+    pub fn sample(&self, x: i32, y: i32) -> Sample {
+        let x = match edge(x, self.width, self.edges.left, self.edges.right) {
+            Ok(x) => x,
+            Err(sample) => return sample,
+        };
+        let y = match edge(y, self.height, self.edges.top, self.edges.bottom) {
+            Ok(y) => y,
+            Err(sample) => return sample,
+        };
+        if self.colors[(x + y * self.width) as usize] == Color::BLACK {
+            Sample::Solid
+        } else {
+            Sample::Open
+        }
+    }
+
+    /// Whether `rect` covers any solid pixel.
+    pub fn solid(&self, rect: Rectangle) -> bool {
+        self.covers(rect, Sample::Solid)
+    }
+
+    /// Whether `rect` reaches into a kill zone.
+    pub fn kills(&self, rect: Rectangle) -> bool {
+        self.covers(rect, Sample::Kill)
+    }
+
+    /// Whether `rect` reaches into a kill zone past an edge or into one of `kill_zones`. Used
+    /// by both the game and the validator, so they agree on what kills.
+    pub fn kills_with(&self, rect: Rectangle, kill_zones: &[Rectangle]) -> bool {
+        self.kills(rect)
+            || kill_zones
+                .iter()
+                .any(|zone| zone.check_collision_recs(&rect))
+    }
+
+    /// Whether `rect` has left the mask entirely past the left, right or bottom edge, once
+    /// wrapped. Used by both the game and the validator, so they agree on what is lost.
+    pub fn fell_out(&self, rect: Rectangle) -> bool {
+        rect.y > self.height as f32 || rect.x + rect.width < 0.0 || rect.x > self.width as f32
+    }
+
+    fn covers(&self, rect: Rectangle, sample: Sample) -> bool {
+        for x in rect.x.floor() as i32..(rect.x + rect.width).ceil() as i32 {
+            for y in rect.y.floor() as i32..(rect.y + rect.height).ceil() as i32 {
+                if self.sample(x, y) == sample {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    /// Brings `rect` back into the mask through wrapping edges once its center has left.
+    pub fn wrap(&self, rect: Rectangle) -> Vector2 {
+        let center = rvec2(rect.x + rect.width / 2.0, rect.y + rect.height / 2.0);
+        let mut position = rvec2(rect.x, rect.y);
+        if center.x < 0.0 && self.edges.left == Edge::Wrap {
+            position.x += self.width as f32;
+        } else if center.x >= self.width as f32 && self.edges.right == Edge::Wrap {
+            position.x -= self.width as f32;
+        }
+        if center.y < 0.0 && self.edges.top == Edge::Wrap {
+            position.y += self.height as f32;
+        } else if center.y >= self.height as f32 && self.edges.bottom == Edge::Wrap {
+            position.y -= self.height as f32;
+        }
+        position
+    }
+
+    /// The mask as an image, e.g. to save it.
+    pub fn image(&self) -> Image {
+        let mut image = Image::gen_image_color(self.width, self.height, Color::BLANK);
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let color = self.colors[(x + y * self.width) as usize];
+                if color != Color::BLANK {
+                    image.draw_pixel(x, y, color);
+                }
+            }
+        }
+        image
+    }
+}
+
+/// Maps a coordinate along one axis into `0..size`, or to what lies past the edge.
+fn edge(value: i32, size: i32, low: Edge, high: Edge) -> Result<i32, Sample> {
+    let edge = if value < 0 {
+        low
+    } else if value >= size {
+        high
+    } else {
+        return Ok(value);
+    };
+    match edge {
+        Edge::Wall => Err(Sample::Solid),
+        Edge::Void => Err(Sample::Open),
+        Edge::Kill => Err(Sample::Kill),
+        Edge::Wrap => Ok(value.rem_euclid(size)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 4x4 white mask with one black pixel just inside each edge, opposite a white one, so
+    /// wrapping can be told apart from clamping.
+    fn mask(edges: Edges) -> Mask {
+        let mut image = Image::gen_image_color(4, 4, Color::WHITE);
+        for (x, y) in [(3, 1), (0, 2), (1, 3), (2, 0)] {
+            image.draw_pixel(x, y, Color::BLACK);
+        }
+        Mask::new(&image, edges)
+    }
+
+    fn edges(set: impl Fn(&mut Edges, Edge), edge: Edge) -> Edges {
+        let mut edges = Edges {
+            left: Edge::Void,
+            right: Edge::Void,
+            top: Edge::Void,
+            bottom: Edge::Void,
+        };
+        set(&mut edges, edge);
+        edges
+    }
+
+    /// Checks the point past an edge for every policy. `solid` and `open` wrap onto a black
+    /// and a white pixel.
+    fn check_edge(set: impl Fn(&mut Edges, Edge) + Copy, solid: (i32, i32), open: (i32, i32)) {
+        let sample = |edge, (x, y)| mask(edges(set, edge)).sample(x, y);
+        assert_eq!(sample(Edge::Wall, solid), Sample::Solid);
+        assert_eq!(sample(Edge::Wall, open), Sample::Solid);
+        assert_eq!(sample(Edge::Void, solid), Sample::Open);
+        assert_eq!(sample(Edge::Kill, solid), Sample::Kill);
+        assert_eq!(sample(Edge::Wrap, solid), Sample::Solid);
+        assert_eq!(sample(Edge::Wrap, open), Sample::Open);
+    }
+
+    #[test]
+    fn left_edge() {
+        check_edge(|edges, edge| edges.left = edge, (-1, 1), (-1, 2));
+    }
+
+    #[test]
+    fn right_edge() {
+        check_edge(|edges, edge| edges.right = edge, (4, 2), (4, 1));
+    }
+
+    #[test]
+    fn top_edge() {
+        check_edge(|edges, edge| edges.top = edge, (1, -1), (2, -1));
+    }
+
+    #[test]
+    fn bottom_edge() {
+        check_edge(|edges, edge| edges.bottom = edge, (2, 4), (1, 4));
+    }
+
+    #[test]
+    fn rect_straddling_an_edge() {
+        // Covers (-1, 1) and (0, 1), which is white.
+        let rect = rrect(-1, 1, 2, 1);
+        let left = |edge| mask(edges(|edges, edge| edges.left = edge, edge));
+        assert!(left(Edge::Wall).solid(rect));
+        assert!(!left(Edge::Wall).kills(rect));
+        assert!(left(Edge::Kill).kills(rect));
+        assert!(!left(Edge::Kill).solid(rect));
+        assert!(!left(Edge::Void).solid(rect));
+        assert!(!left(Edge::Void).kills(rect));
+        // Wraps onto (3, 1), which is black.
+        assert!(left(Edge::Wrap).solid(rect));
+
+        // Covers (0, 3) and (0, 4).
+        let rect = rrect(0, 3, 1, 2);
+        let bottom = |edge| mask(edges(|edges, edge| edges.bottom = edge, edge));
+        assert!(bottom(Edge::Kill).kills(rect));
+        assert!(!bottom(Edge::Void).kills(rect));
+        // Wraps onto (0, 0), which is white.
+        assert!(!bottom(Edge::Wrap).solid(rect));
+    }
+
+    #[test]
+    fn wrap_once_the_center_crosses() {
+        let wrapping = mask(Edges {
+            left: Edge::Wrap,
+            right: Edge::Wrap,
+            top: Edge::Wrap,
+            bottom: Edge::Wrap,
+        });
+        let position = |rect| {
+            let Vector2 { x, y } = wrapping.wrap(rect);
+            (x, y)
+        };
+        // Centers still inside stay put.
+        assert_eq!(position(rrect(-1, 0, 4, 1)), (-1.0, 0.0));
+        assert_eq!(position(rrect(2, 1, 2, 2)), (2.0, 1.0));
+        // Centers past an edge come back through the opposite one.
+        assert_eq!(position(rrect(-3, 0, 4, 1)), (1.0, 0.0));
+        assert_eq!(position(rrect(3, 0, 4, 1)), (-1.0, 0.0));
+        assert_eq!(position(rrect(0, -3, 1, 4)), (0.0, 1.0));
+        assert_eq!(position(rrect(0, 3, 1, 4)), (0.0, -1.0));
+
+        // Other edges never move the rect.
+        let walled = mask(edges(|edges, edge| edges.left = edge, Edge::Wall));
+        let Vector2 { x, y } = walled.wrap(rrect(-3, 0, 4, 1));
+        assert_eq!((x, y), (-3.0, 0.0));
+    }
+
+    #[test]
+    fn fell_out_past_left_right_and_bottom() {
+        let mask = mask(edges(|edges, edge| edges.left = edge, Edge::Void));
+        assert!(!mask.fell_out(rrect(-1, 3, 2, 2)));
+        assert!(mask.fell_out(rrect(-3, 0, 2, 1)));
+        assert!(mask.fell_out(rrect(5, 0, 2, 1)));
+        assert!(mask.fell_out(rrect(0, 5, 1, 2)));
+        // Above the top is still in the level, e.g. at the top of a jump.
+        assert!(!mask.fell_out(rrect(0, -5, 1, 2)));
+    }
+}
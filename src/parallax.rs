@@ -0,0 +1,55 @@
+use std::rc::Rc;
+
+use crate::assets::*;
+use crate::manifest::*;
+use crate::resources::*;
+
+pub struct ParallaxLayer {
+    info: LayerInfo,
+    textures: Rc<Animation>,
+    animation: AnimationPlayer,
+    time: f32,
+}
+
+impl ParallaxLayer {
+    pub fn new(assets: &Assets, info: &LayerInfo) -> Self {
+        Self {
+            info: info.clone(),
+            textures: assets.animation(&info.animation),
+            animation: AnimationPlayer::new(info.clip.as_deref().unwrap_or_default()),
+            time: 0.0,
+        }
+    }
+
+    pub fn foreground(&self) -> bool {
+        self.info.foreground
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.animation.update(dt, &self.textures);
+    }
+
+    pub(crate) fn draw(&self, d: &mut impl RaylibDraw, camera: Camera2D) {
+        let view = camera.target - camera.offset / camera.zoom;
+        let scroll = rvec2(self.info.scroll[0], self.info.scroll[1]);
+        let mut position = rvec2(self.info.offset[0], self.info.offset[1])
+            + view * (rvec2(1, 1) - scroll)
+            + rvec2(self.info.velocity[0], self.info.velocity[1]) * self.time;
+        position = rvec2(position.x.floor(), position.y.floor());
+
+        let frame = self.animation.frame(&self.textures);
+        if !self.info.repeat {
+            self.textures.draw(d, frame, position, false);
+            return;
+        }
+
+        let width = self.textures.size().x.max(1.0);
+        let right = view.x + camera.offset.x * 2.0 / camera.zoom;
+        let mut x = position.x + ((view.x - position.x) / width).floor() * width;
+        while x <= right {
+            self.textures.draw(d, frame, rvec2(x, position.y), false);
+            x += width;
+        }
+    }
+}
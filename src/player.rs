@@ -1,66 +1,79 @@
+use std::rc::Rc;
+
+use crate::assets::*;
+use crate::ecs::*;
+use crate::events::*;
 use crate::resources::*;
 use crate::scene::*;
+use crate::viewport::*;
 
-pub struct Player {
-    rect: Rectangle,
-    velocity: Vector2,
-    jumps: u8,
+/// One frame of player controls, read from the keyboard and mouse or produced by a bot.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PlayerInput {
+    /// From -1 (left) to 1 (right).
+    pub direction: f32,
+    pub jump: bool,
+    pub release_jump: bool,
+    pub shoot: bool,
+    /// World position being aimed at.
+    pub aim: Vector2,
+}
 
-    textures: Animation,
-    camera: Camera2D,
-    frame: f32,
-    flip: i8,
+impl PlayerInput {
+    pub fn read(rl: &RaylibHandle, viewport: &Viewport, camera: Camera2D) -> Self {
+        Self {
+            direction: (rl.is_key_down(KeyboardKey::KEY_D) as i32
+                - rl.is_key_down(KeyboardKey::KEY_A) as i32) as f32,
+            jump: rl.is_key_pressed(KeyboardKey::KEY_SPACE),
+            release_jump: rl.is_key_released(KeyboardKey::KEY_SPACE),
+            shoot: rl.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON),
+            aim: viewport.screen_to_world(rl.get_mouse_position(), camera),
+        }
+    }
+}
 
-    shoot_sound: Sound,
-    jump_sound: Sound,
+/// What happened during a [`Body::step`].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Step {
+    pub jumped: bool,
+    /// Vertical speed the body hit the ground with, if it landed.
+    pub landed: Option<f32>,
 }
 
-impl Player {
-    pub(crate) fn new(rl: &mut RaylibHandle, thread: &RaylibThread, pos: Vector2) -> Self {
-        let textures = Animation::load(rl, thread, "Assets/Player");
-        let size = textures.size();
+/// The player's movement and terrain collision, kept apart from the scene, input devices and
+/// drawing so it can also be simulated without a window.
+#[derive(Clone, Copy, Debug)]
+pub struct Body {
+    pub rect: Rectangle,
+    pub velocity: Vector2,
+    pub jumps: u8,
+    pub grounded: bool,
+}
+
+impl Body {
+    pub fn new(rect: Rectangle) -> Self {
         Self {
-            rect: rrect(pos.x, pos.y, size.x, size.y),
+            rect,
             velocity: Vector2::zero(),
             jumps: 0,
-
-            textures,
-            camera: Camera2D {
-                target: pos + size / 2.0,
-                offset: rvec2(rl.get_screen_width(), rl.get_screen_height()) / 2.0,
-                rotation: 0.0,
-                zoom: 1.0,
-            },
-            frame: 0.0,
-            flip: 1,
-
-            shoot_sound: Sound::load_sound("Assets/Shoot.wav").expect("Failed to load sound!"),
-            jump_sound: Sound::load_sound("Assets/Jump.wav").expect("Failed to load sound!"),
-        }
-    }
-
-    fn collides(&self, scene: &Scene) -> bool {
-        if let Some(mut rect) = scene.tower().shield_rect() {
-            rect.y = 0.0;
-            rect.height = scene.height() as _;
-            if self.rect.check_collision_recs(&rect) {
-                return true;
-            }
-        }
-        for x in self.rect.x.floor() as i32..(self.rect.x + self.rect.width).ceil() as i32 {
-            for y in self.rect.y.floor() as i32..(self.rect.y + self.rect.height).ceil() as i32 {
-                if scene.metadata(rvec2(x, y)) == Color::BLACK {
-                    return true;
-                }
-            }
+            grounded: false,
         }
-        false
     }
 
-    fn resolve_collision(&mut self, scene: &Scene, scale: f32, dir: Vector2, undo: bool) -> f32 {
+    /// Moves the body back out of terrain along `dir`, in steps of half a screen pixel.
+    /// Returns how far it moved, or infinity (without moving) if it would leave `bounds`
+    /// before getting free.
+    pub fn resolve_collision(
+        &mut self,
+        scale: f32,
+        dir: Vector2,
+        undo: bool,
+        bounds: Rectangle,
+        solid: &impl Fn(Rectangle) -> bool,
+    ) -> f32 {
         let mut offset = 0.0;
-        while self.collides(scene) {
-            if !rrect(0, 0, scene.width(), scene.height()).check_collision_recs(&self.rect) {
+        while solid(self.rect) {
+            if !bounds.check_collision_recs(&self.rect) {
                 self.rect.x -= dir.x * offset;
                 self.rect.y -= dir.y * offset;
                 return f32::INFINITY;
@@ -77,112 +90,226 @@ impl Player {
         offset
     }
 
-    pub(crate) fn update(&mut self, rl: &RaylibHandle, audio: &mut RaylibAudio, scene: &mut Scene) {
+    /// Advances the body by `dt`. `solid` tells whether a rectangle overlaps terrain and
+    /// `scale` is the number of screen pixels per world pixel.
+    pub fn step(
+        &mut self,
+        input: &PlayerInput,
+        dt: f32,
+        scale: f32,
+        bounds: Rectangle,
+        solid: &impl Fn(Rectangle) -> bool,
+    ) -> Step {
         let speed = 100.0;
         let rate = 0.1;
         let gravity = 400.0;
         let jump = -200.0;
         let cut = 0.5;
-        let scale = rl.get_screen_height() as f32 / scene.height() as f32;
         let jumps = 2;
-        let bullet_speed = 1.0;
-
-        // * Shoot
-        if rl.is_mouse_button_pressed(MouseButton::MOUSE_LEFT_BUTTON) {
-            let gun = self.position() + rvec2(if self.flip < 0 { 4 } else { 6 }, 11);
-            let aim = rl.get_screen_to_world2D(rl.get_mouse_position(), self.camera) - gun;
 
-            scene.bullets.push(Bullet::new(
-                gun,
-                aim.normalized() * (aim.length() * 0.5 + 80.0) * bullet_speed,
-            ));
-            audio.play_sound(&self.shoot_sound);
-        }
-
-        // * Movement
+        let mut step = Step::default();
         self.velocity.x = lerp(
             self.velocity.x,
-            (rl.is_key_down(KeyboardKey::KEY_D) as i32 - rl.is_key_down(KeyboardKey::KEY_A) as i32)
-                as f32
-                * speed,
-            rl.get_frame_time() / rate,
+            input.direction.clamp(-1.0, 1.0) * speed,
+            dt / rate,
         );
 
-        self.velocity.y += gravity * rl.get_frame_time();
-        if rl.is_key_pressed(KeyboardKey::KEY_SPACE) && self.jumps > 0 {
+        self.velocity.y += gravity * dt;
+        if input.jump && self.jumps > 0 {
             self.velocity.y = jump;
             self.jumps -= 1;
-            audio.play_sound(&self.jump_sound);
+            step.jumped = true;
         }
-        if rl.is_key_released(KeyboardKey::KEY_SPACE) && self.velocity.y < 0.0 {
+        if input.release_jump && self.velocity.y < 0.0 {
             self.velocity.y *= cut;
         }
 
-        let motion = self.velocity * rl.get_frame_time();
+        let motion = self.velocity * dt;
         self.rect.x += motion.x;
-        if self.collides(scene) {
-            let step = self.resolve_collision(scene, scale, rvec2(0, -1), true);
-            let step_ratio = motion.x.abs().ceil() / step;
+        if solid(self.rect) {
+            let climb = self.resolve_collision(scale, rvec2(0, -1), true, bounds, solid);
+            let step_ratio = motion.x.abs().ceil() / climb;
 
             if step_ratio >= 1.0 {
-                self.rect.y -= step;
+                self.rect.y -= climb;
             } else {
                 self.velocity.x = 0.0;
-                self.resolve_collision(scene, scale, rvec2(-motion.x.signum(), 0), false);
+                self.resolve_collision(scale, rvec2(-motion.x.signum(), 0), false, bounds, solid);
             }
         }
 
         self.rect.y += motion.y;
-        if self.collides(scene) {
+        let was_grounded = self.grounded;
+        self.grounded = false;
+        if solid(self.rect) {
             if self.velocity.y > 0.0 {
                 self.jumps = jumps;
+                self.grounded = true;
             }
+            let speed = self.velocity.y;
             self.velocity.y = 0.0;
-            self.resolve_collision(scene, scale, rvec2(0, -motion.y.signum()), false);
+            self.resolve_collision(scale, rvec2(0, -motion.y.signum()), false, bounds, solid);
+
+            if self.grounded && !was_grounded {
+                step.landed = Some(speed);
+            }
         }
+        step
+    }
 
-        // * Camera
-        self.camera.zoom = scale;
+    pub fn position(&self) -> Vector2 {
+        rvec2(self.rect.x, self.rect.y)
+    }
+}
 
-        self.camera.offset = rvec2(rl.get_screen_width(), rl.get_screen_height()) / 2.0;
-        self.camera.target = self.center();
+/// Whether `rect` overlaps the scene's terrain, or the column the shield blocks.
+pub fn collides(scene: &Scene, rect: Rectangle) -> bool {
+    if let Some(mut shield) = scene.tower().shield_rect() {
+        shield.y = 0.0;
+        shield.height = scene.height() as _;
+        if rect.check_collision_recs(&shield) {
+            return true;
+        }
+    }
+    scene.mask().solid(rect)
+}
 
-        self.camera.target.x = self.camera.target.x.clamp(
-            self.camera.offset.x / scale,
-            scene.width() as f32 - self.camera.offset.x / scale,
-        );
-        self.camera.target.y = self.camera.target.y.clamp(
-            self.camera.offset.y / scale,
-            scene.height() as f32 - self.camera.offset.y / scale,
-        );
+/// Seconds the player can't die again after respawning.
+const INVULNERABILITY: f32 = 2.0;
 
-        // * Animation
-        if self.velocity.x.abs() > 0.5 {
-            self.frame = (self.frame + rl.get_frame_time() * 5.0) % 2.0;
+pub struct Player {
+    body: Body,
+    invulnerable: f32,
+
+    textures: Rc<Animation>,
+    animation: AnimationPlayer,
+    flip: i8,
+}
+
+impl Player {
+    pub(crate) fn new(assets: &Assets, pos: Vector2) -> Self {
+        let textures = assets.animation("Player");
+        let size = textures.size();
+        Self {
+            body: Body::new(rrect(pos.x, pos.y, size.x, size.y)),
+            invulnerable: 0.0,
+
+            textures,
+            animation: AnimationPlayer::new("idle"),
+            flip: 1,
         }
+    }
 
-        if rl.get_mouse_x() > rl.get_world_to_screen2D(self.position(), self.camera).x as i32 {
-            self.flip = 1;
-        } else {
-            self.flip = -1;
+    /// Picks up a reloaded sprite and moves the player out of any terrain that appeared
+    /// under them, back to `spawn` if there is no way out.
+    pub fn reload(&mut self, assets: &Assets, scene: &Scene, spawn: Vector2) {
+        self.textures = assets.animation("Player");
+        let size = self.textures.size();
+        self.body.rect.width = size.x;
+        self.body.rect.height = size.y;
+
+        let solid = |rect| collides(scene, rect);
+        if solid(self.body.rect)
+            && self.body.resolve_collision(
+                1.0,
+                rvec2(0, -1),
+                false,
+                rrect(0, 0, scene.width(), scene.height()),
+                &solid,
+            ) == f32::INFINITY
+        {
+            self.teleport(spawn);
         }
     }
 
-    pub(crate) fn draw(&self, d: &mut RaylibMode2D<RaylibDrawHandle>) {
-        d.draw_texture_rec(
-            &self.textures[self.frame as usize],
-            rrect(0, 0, self.rect.width * self.flip as f32, self.rect.height),
+    pub(crate) fn update(&mut self, input: &PlayerInput, dt: f32, scale: f32, scene: &mut Scene) {
+        let bullet_speed = 1.0;
+
+        // * Shoot
+        if input.shoot {
+            let gun = self.position() + rvec2(if self.flip < 0 { 4 } else { 6 }, 11);
+            let aim = input.aim - gun;
+
+            scene.spawn_bullet(
+                gun,
+                aim.normalized() * (aim.length() * 0.5 + 80.0) * bullet_speed,
+                Owner::Player,
+            );
+            self.animation.trigger("shoot");
+        }
+
+        // * Movement
+        let bounds = rrect(0, 0, scene.width(), scene.height());
+        let step = self
+            .body
+            .step(input, dt, scale, bounds, &|rect| collides(scene, rect));
+        if step.jumped {
+            scene.events.push(GameEvent::PlayerJumped {
+                position: self.center(),
+            });
+        }
+        if let Some(speed) = step.landed {
+            scene.events.push(GameEvent::PlayerLanded {
+                position: self.position()
+                    + rvec2(self.body.rect.width / 2.0, self.body.rect.height),
+                speed,
+            });
+        }
+        let position = scene.mask().wrap(self.body.rect);
+        self.body.rect.x = position.x;
+        self.body.rect.y = position.y;
+
+        // * Death
+        scene.reach_checkpoint(self.body.rect);
+        self.invulnerable = (self.invulnerable - dt).max(0.0);
+        let rect = self.body.rect;
+        if scene.mask().fell_out(rect) || (self.invulnerable <= 0.0 && scene.kills(rect)) {
+            scene.player_died(self.center());
+            self.teleport(scene.respawn_point(self.size()));
+            self.invulnerable = INVULNERABILITY;
+            self.animation.trigger("hurt");
+        }
+
+        // * Animation
+        self.animation.play(if !self.body.grounded {
+            if self.body.velocity.y < 0.0 {
+                "jump"
+            } else {
+                "fall"
+            }
+        } else if self.body.velocity.x.abs() > 0.5 {
+            "run"
+        } else {
+            "idle"
+        });
+        self.animation.update(dt, &self.textures);
+
+        self.flip = if input.aim.x > self.position().x {
+            1
+        } else {
+            -1
+        };
+    }
+
+    pub(crate) fn draw(&self, d: &mut impl RaylibDraw) {
+        // Blinks while invulnerable.
+        if (self.invulnerable * 10.0) as i32 % 2 == 1 {
+            return;
+        }
+        self.textures.draw(
+            d,
+            self.animation.frame(&self.textures),
             self.position(),
-            Color::WHITE,
+            self.flip < 0,
         );
     }
 
     pub fn position(&self) -> Vector2 {
-        rvec2(self.rect.x, self.rect.y)
+        self.body.position()
     }
 
     pub fn size(&self) -> Vector2 {
-        rvec2(self.rect.width, self.rect.height)
+        rvec2(self.body.rect.width, self.body.rect.height)
     }
 
     pub fn center(&self) -> Vector2 {
@@ -190,10 +317,20 @@ impl Player {
     }
 
     pub fn rect(&self) -> Rectangle {
-        self.rect
+        self.body.rect
+    }
+
+    pub fn body(&self) -> &Body {
+        &self.body
+    }
+
+    pub fn invulnerable(&self) -> bool {
+        self.invulnerable > 0.0
     }
 
-    pub fn camera(&self) -> Camera2D {
-        self.camera
+    pub fn teleport(&mut self, position: Vector2) {
+        self.body.rect.x = position.x;
+        self.body.rect.y = position.y;
+        self.body.velocity = Vector2::zero();
     }
 }
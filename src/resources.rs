@@ -1,45 +1,340 @@
-use std::ops::Index;
+use std::collections::HashMap;
 
 pub use raylib::prelude::*;
+use serde::Deserialize;
+
+/// A random float between `min` and `max`, in thousandths of the range.
+pub fn random(min: f32, max: f32) -> f32 {
+    min + get_random_value::<i32>(0, 1000) as f32 / 1000.0 * (max - min)
+}
+
+/// Seeded generator (xorshift64*) for game logic, so a run repeats exactly from its seed
+/// whatever effects, camera shake and audio draw from raylib's shared one.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // Any seed works, but the state must never be zero.
+        Self {
+            state: seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1,
+        }
+    }
+
+    fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A random integer between `min` and `max`, both included, like `get_random_value`.
+    pub fn int(&mut self, min: i32, max: i32) -> i32 {
+        let (min, max) = (min.min(max), min.max(max));
+        let span = (max as i64 - min as i64 + 1) as u64;
+        (min as i64 + ((self.next() >> 32) % span) as i64) as i32
+    }
+
+    /// A random float between `min` and `max`.
+    pub fn float(&mut self, min: f32, max: f32) -> f32 {
+        min + (self.next() >> 40) as f32 / (1u64 << 24) as f32 * (max - min)
+    }
+}
+
+/// A seed from the clock, for runs nobody needs to repeat.
+pub fn clock_seed() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|time| time.as_nanos() as u64)
+        .unwrap_or_default()
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LoopMode {
+    Loop,
+    Once,
+    PingPong,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Frame {
+    pub source: Rectangle,
+    pub duration: f32,
+}
+
+/// A named run of frames in the sheet, already ordered by playback direction.
+#[derive(Clone, Debug)]
+pub struct Clip {
+    pub frames: Vec<usize>,
+    pub mode: LoopMode,
+}
+
+impl Clip {
+    fn sequence(&self) -> Vec<usize> {
+        let mut sequence = self.frames.clone();
+        if self.mode == LoopMode::PingPong && self.frames.len() > 2 {
+            sequence.extend(self.frames[1..self.frames.len() - 1].iter().rev());
+        }
+        sequence
+    }
+
+    pub fn length(&self, frames: &[Frame]) -> f32 {
+        self.sequence().iter().map(|&i| frames[i].duration).sum()
+    }
+
+    pub fn frame(&self, frames: &[Frame], time: f32) -> usize {
+        let sequence = self.sequence();
+        let length = self.length(frames);
+        let mut time = match self.mode {
+            LoopMode::Once => time.min(length),
+            _ if length > 0.0 => time % length,
+            _ => 0.0,
+        };
+        for &i in &sequence {
+            if time < frames[i].duration {
+                return i;
+            }
+            time -= frames[i].duration;
+        }
+        sequence.last().copied().unwrap_or(0)
+    }
+}
+
+// * Aseprite JSON atlas ("Array" frame export)
+#[derive(Deserialize)]
+struct Atlas {
+    frames: Vec<AtlasFrame>,
+    #[serde(default)]
+    meta: AtlasMeta,
+}
+
+#[derive(Deserialize)]
+struct AtlasFrame {
+    frame: AtlasRect,
+    #[serde(default = "default_duration")]
+    duration: u32,
+}
+
+#[derive(Deserialize)]
+struct AtlasRect {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+}
+
+#[derive(Default, Deserialize)]
+struct AtlasMeta {
+    #[serde(default, rename = "frameTags")]
+    frame_tags: Vec<AtlasTag>,
+}
+
+#[derive(Deserialize)]
+struct AtlasTag {
+    name: String,
+    from: usize,
+    to: usize,
+    #[serde(default)]
+    direction: String,
+    repeat: Option<String>,
+}
+
+fn default_duration() -> u32 {
+    100
+}
+
+/// Size of the first frame of an atlas, for code that needs sprite sizes without loading
+/// textures.
+pub fn atlas_frame_size(atlas: &[u8]) -> Result<Vector2, String> {
+    let atlas: Atlas =
+        serde_json::from_slice(atlas).map_err(|err| format!("Invalid atlas: {}", err))?;
+    let frame = atlas.frames.first().ok_or("Atlas has no frames")?;
+    Ok(rvec2(frame.frame.w, frame.frame.h))
+}
+
+/// A texture to draw, or only its size when running without a window, since textures can't
+/// be made without one.
+pub struct Picture {
+    texture: Option<Texture2D>,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Picture {
+    pub fn new(texture: Texture2D) -> Self {
+        Self {
+            width: texture.width,
+            height: texture.height,
+            texture: Some(texture),
+        }
+    }
+
+    /// The size of `image` with nothing to draw.
+    pub fn headless(image: &Image) -> Self {
+        Self {
+            texture: None,
+            width: image.width(),
+            height: image.height(),
+        }
+    }
+
+    pub fn texture(&self) -> Option<&Texture2D> {
+        self.texture.as_ref()
+    }
+}
 
 pub struct Animation {
-    textures: Vec<Texture2D>,
+    texture: Picture,
+    frames: Vec<Frame>,
+    clips: HashMap<String, Clip>,
 }
 
 impl Animation {
-    pub fn load(rl: &mut RaylibHandle, thread: &RaylibThread, path: &str) -> Self {
-        let mut textures = Vec::new();
-        for i in 1..usize::MAX {
-            let filename = &format!("{}{}.png", path, i);
-            if !std::path::Path::new(filename).exists() {
-                break;
+    /// A single still frame covering the whole texture.
+    pub fn still(texture: Picture) -> Self {
+        Self {
+            frames: vec![Frame {
+                source: rrect(0, 0, texture.width, texture.height),
+                duration: 0.1,
+            }],
+            texture,
+            clips: HashMap::new(),
+        }
+    }
+
+    pub fn from_atlas(texture: Picture, atlas: &[u8]) -> Result<Self, String> {
+        let atlas: Atlas =
+            serde_json::from_slice(atlas).map_err(|err| format!("Invalid atlas: {}", err))?;
+        if atlas.frames.is_empty() {
+            return Err("Atlas has no frames".to_owned());
+        }
+
+        let frames = atlas
+            .frames
+            .iter()
+            .map(|frame| Frame {
+                source: rrect(frame.frame.x, frame.frame.y, frame.frame.w, frame.frame.h),
+                duration: frame.duration as f32 / 1000.0,
+            })
+            .collect::<Vec<_>>();
+
+        let mut clips = HashMap::new();
+        for tag in atlas.meta.frame_tags {
+            if tag.from > tag.to || tag.to >= frames.len() {
+                return Err(format!("Clip {} is out of range", tag.name));
             }
-            textures.push(
-                rl.load_texture(thread, filename)
-                    .expect("Failed to load texture"),
+            let mut clip_frames = (tag.from..=tag.to).collect::<Vec<_>>();
+            if tag.direction == "reverse" {
+                clip_frames.reverse();
+            }
+            let mode = match (tag.repeat.as_deref(), tag.direction.as_str()) {
+                (Some("1"), _) => LoopMode::Once,
+                (_, "pingpong") => LoopMode::PingPong,
+                _ => LoopMode::Loop,
+            };
+            clips.insert(
+                tag.name,
+                Clip {
+                    frames: clip_frames,
+                    mode,
+                },
             );
         }
 
-        Self { textures }
+        Ok(Self {
+            texture,
+            frames,
+            clips,
+        })
+    }
+
+    pub fn clip(&self, name: &str) -> Option<&Clip> {
+        self.clips.get(name)
+    }
+
+    pub fn clip_length(&self, name: &str) -> f32 {
+        self.clip(name)
+            .map_or(0.0, |clip| clip.length(&self.frames))
+    }
+
+    /// Frame of the clip at `time` seconds into it, or the first frame if there is no such clip.
+    pub fn frame(&self, name: &str, time: f32) -> usize {
+        self.clip(name)
+            .map_or(0, |clip| clip.frame(&self.frames, time))
+    }
+
+    pub fn draw(&self, d: &mut impl RaylibDraw, frame: usize, position: Vector2, flip: bool) {
+        let mut source = self.frames[frame.min(self.frames.len() - 1)].source;
+        if flip {
+            source.width *= -1.0;
+        }
+        if let Some(texture) = self.texture.texture() {
+            d.draw_texture_rec(texture, source, position, Color::WHITE);
+        }
     }
 
     pub fn width(&self) -> i32 {
-        self.textures[0].width
+        self.frames[0].source.width as _
     }
 
     pub fn height(&self) -> i32 {
-        self.textures[0].height
+        self.frames[0].source.height as _
     }
 
     pub fn size(&self) -> Vector2 {
-        rvec2(self.textures[0].width, self.textures[0].height)
+        rvec2(self.frames[0].source.width, self.frames[0].source.height)
     }
 }
 
-impl Index<usize> for Animation {
-    type Output = Texture2D;
+/// Plays a looping state clip, with one-shot clips (hurt, shoot) layered on top until
+/// they finish.
+#[derive(Clone, Debug)]
+pub struct AnimationPlayer {
+    state: String,
+    one_shot: Option<String>,
+    time: f32,
+}
+
+impl AnimationPlayer {
+    pub fn new(state: &str) -> Self {
+        Self {
+            state: state.to_owned(),
+            one_shot: None,
+            time: 0.0,
+        }
+    }
+
+    pub fn play(&mut self, state: &str) {
+        if self.state != state {
+            self.state = state.to_owned();
+            if self.one_shot.is_none() {
+                self.time = 0.0;
+            }
+        }
+    }
+
+    pub fn trigger(&mut self, clip: &str) {
+        self.one_shot = Some(clip.to_owned());
+        self.time = 0.0;
+    }
+
+    pub fn update(&mut self, dt: f32, animation: &Animation) {
+        self.time += dt;
+        if let Some(one_shot) = &self.one_shot {
+            if self.time >= animation.clip_length(one_shot) {
+                self.one_shot = None;
+                self.time = 0.0;
+            }
+        }
+    }
+
+    pub fn current(&self) -> &str {
+        self.one_shot.as_deref().unwrap_or(&self.state)
+    }
 
-    fn index(&self, index: usize) -> &Self::Output {
-        &self.textures[index]
+    pub fn frame(&self, animation: &Animation) -> usize {
+        animation.frame(self.current(), self.time)
     }
 }
@@ -1,144 +1,372 @@
+use std::rc::Rc;
+
+use crate::assets::*;
+use crate::ecs::*;
+use crate::events::*;
+use crate::manifest::*;
+use crate::mask::*;
+use crate::parallax::*;
 use crate::resources::*;
+use crate::score::*;
 use crate::tower::*;
 
+/// Height of the flag drawn at checkpoints, which the player has to touch.
+const FLAG_HEIGHT: f32 = 14.0;
+
 pub struct Scene {
-    texture: Texture2D,
-    metadata: ImageColors,
+    texture: Rc<Picture>,
+    mask: Mask,
+    layers: Vec<ParallaxLayer>,
     tower: Tower,
-    pub bullets: Vec<Bullet>,
-}
+    target: Option<Vector2>,
+    spawn: Vector2,
+    checkpoints: Vec<Vector2>,
+    checkpoint: Option<usize>,
+    kill_zones: Vec<Rectangle>,
+    death_penalty: f32,
+    pub world: World,
+    pub events: Events,
+    /// Drives everything random in the simulation.
+    pub rng: Rng,
 
-pub struct Bullet {
-    position: Vector2,
-    velocity: Vector2,
+    bullet_texture: Rc<Picture>,
+    tower_entity: Entity,
+    shield_entity: Option<Entity>,
 }
 
-impl Bullet {
-    pub fn new(position: Vector2, velocity: Vector2) -> Self {
-        Self { position, velocity }
-    }
+impl Scene {
+    pub fn new(assets: &Assets, level: &LevelInfo, seed: u64) -> Self {
+        let texture = assets.texture(&level.art);
+        let mask = load_mask(assets, level, &texture);
 
-    pub(self) fn update(&mut self, rl: &RaylibHandle) {
-        self.position += self.velocity * rl.get_frame_time();
-    }
+        let tower = Tower::new(assets, level);
+        let mut world = World::new();
 
-    pub fn position(&self) -> Vector2 {
-        self.position
-    }
-}
+        let tower_entity = world.spawn();
+        world
+            .positions
+            .insert(tower_entity, Position(tower.position()));
+        world.colliders.insert(
+            tower_entity,
+            Collider {
+                size: tower.size(),
+                layer: Layer::Tower,
+            },
+        );
+        world.owners.insert(tower_entity, Owner::Scene);
 
-#[allow(dead_code)]
-impl Scene {
-    pub fn new(rl: &mut RaylibHandle, thread: &RaylibThread, index: usize) -> Self {
         Self {
-            texture: rl
-                .load_texture(thread, &format!("Assets/Scene{}-1.png", index))
-                .expect("Failed to load scene texture."),
-            metadata: Image::load_image(&format!("Assets/Scene{}-2.png", index))
-                .expect("Failed to load scene metadata.")
-                .get_image_data(),
-            tower: Tower::new(rl, thread, rvec2(115, 48), index),
-            bullets: Vec::new(),
+            texture,
+            mask,
+            layers: load_layers(assets, level),
+            tower,
+            target: None,
+            spawn: level.spawn(),
+            checkpoints: level.checkpoints(),
+            checkpoint: None,
+            kill_zones: level.kill_zones(),
+            death_penalty: level.death_penalty,
+            world,
+            events: Events::default(),
+            rng: Rng::new(seed),
+
+            bullet_texture: assets.texture("Bullet"),
+            tower_entity,
+            shield_entity: None,
         }
     }
 
-    pub(crate) fn update(
-        &mut self,
-        rl: &mut RaylibHandle,
-        audio: &mut RaylibAudio,
-        bullet_texture: &Texture2D,
-        game_over: &mut bool,
-    ) {
-        self.tower.update(
-            rl,
-            self.bullets
-                .iter()
-                .filter(|bullet| {
-                    let center = bullet.position()
-                        + rvec2(bullet_texture.width(), bullet_texture.height()) / 2.0;
-                    (if self.tower.flipped() {
-                        center.x < self.tower.position().x
-                    } else {
-                        center.x > self.tower.position().x + self.tower.size().x
-                    }) && center.y > self.tower.position().y
-                        && center.y < self.tower.position().y + self.tower.size().y
-                })
-                .min_by_key(|bullet| {
-                    if self.tower.flipped() {
-                        -(bullet.position().x as i32 + bullet_texture.width() / 2)
-                    } else {
-                        bullet.position().x as i32 + bullet_texture.width() / 2
-                    }
-                })
-                .map(|bullet| {
-                    bullet.position() + rvec2(bullet_texture.width(), bullet_texture.height()) / 2.0
-                }),
-            game_over,
+    pub fn spawn_bullet(&mut self, position: Vector2, velocity: Vector2, owner: Owner) -> Entity {
+        let bullet = self.world.spawn();
+        self.world.positions.insert(bullet, Position(position));
+        self.world.velocities.insert(bullet, Velocity(velocity));
+        self.world.colliders.insert(
+            bullet,
+            Collider {
+                size: rvec2(self.bullet_texture.width, self.bullet_texture.height),
+                layer: Layer::Projectile,
+            },
+        );
+        self.world.sprites.insert(
+            bullet,
+            Sprite {
+                texture: self.bullet_texture.clone(),
+                flip: false,
+            },
         );
+        self.world.owners.insert(bullet, owner);
+        self.world
+            .bounds
+            .insert(bullet, OutOfBounds::Ricochet { chance: 5 });
+        self.events.push(GameEvent::BulletFired {
+            owner,
+            position,
+            velocity,
+        });
+        bullet
+    }
 
-        for i in (0..self.bullets.len()).rev() {
-            self.bullets[i].update(rl);
-            let rect = rrect(
-                self.bullets[i].position().x,
-                self.bullets[i].position().y,
-                bullet_texture.width(),
-                bullet_texture.height(),
-            );
-            if !rrect(0, 0, self.texture.width, self.texture.height).check_collision_recs(&rect) {
-                if (rect.y < 0.0 || rect.y + rect.height > self.texture.height as f32)
-                    && get_random_value::<i32>(0, 5) == 0
-                {
-                    if rect.y < 0.0 {
-                        self.bullets[i].position.y = 0.0;
-                        self.bullets[i].velocity.y *= -1.0;
-                    } else {
-                        self.bullets[i].position.y = self.texture.height as f32 - rect.height;
-                        self.bullets[i].velocity.y *= -1.0;
-                    }
+    pub fn bullets(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.world.with_layer(Layer::Projectile)
+    }
+
+    pub(crate) fn update(&mut self, dt: f32) {
+        for layer in &mut self.layers {
+            layer.update(dt);
+        }
+
+        self.target = self
+            .bullets()
+            .filter_map(|bullet| self.world.center(bullet))
+            .filter(|center| {
+                (if self.tower.flipped() {
+                    center.x < self.tower.position().x
                 } else {
-                    self.bullets.remove(i);
-                }
-            } else if self.tower.rect().check_collision_recs(&rect) {
-                self.bullets.remove(i);
-                self.tower.hit(audio);
-            } else if let Some(rect2) = self.tower.shield_rect() {
-                if rect2.check_collision_recs(&rect) {
-                    self.bullets.remove(i);
-                    self.tower.hit_shield();
+                    center.x > self.tower.position().x + self.tower.size().x
+                }) && center.y > self.tower.position().y
+                    && center.y < self.tower.position().y + self.tower.size().y
+            })
+            .min_by_key(|center| {
+                if self.tower.flipped() {
+                    -(center.x as i32)
+                } else {
+                    center.x as i32
                 }
+            });
+        self.tower.update(dt, self.target, &mut self.events);
+        self.sync_tower();
+
+        systems::movement(&mut self.world, dt);
+        systems::bounds(
+            &mut self.world,
+            rrect(0, 0, self.texture.width, self.texture.height),
+            &mut self.rng,
+        );
+
+        for (a, b) in systems::contacts(&self.world) {
+            if !self.world.is_alive(a) || !self.world.is_alive(b) {
+                continue;
+            }
+            let layer = |entity| {
+                self.world
+                    .colliders
+                    .get(entity)
+                    .map(|collider| collider.layer)
+            };
+            let (bullet, other) = if layer(a) == Some(Layer::Projectile) {
+                (a, b)
             } else {
-                for j in i..self.bullets.len() {
-                    if j != i {
-                        let rect2 = rrect(
-                            self.bullets[j].position().x,
-                            self.bullets[j].position().y,
-                            bullet_texture.width(),
-                            bullet_texture.height(),
-                        );
-                        if rect.check_collision_recs(&rect2) {
-                            self.bullets.remove(i.max(j));
-                            self.bullets.remove(i.min(j));
-                            break;
-                        }
+                (b, a)
+            };
+
+            let position = self.world.center(bullet).unwrap_or_default();
+            match layer(other) {
+                Some(Layer::Projectile) => {
+                    let kind = self.interception(bullet, other);
+                    self.world.despawn(bullet);
+                    self.world.despawn(other);
+                    self.events.push(GameEvent::BulletsAnnihilated { position });
+                    if let Some(kind) = kind {
+                        self.events.push(GameEvent::Scored {
+                            kind,
+                            points: kind.points(),
+                            position,
+                        });
                     }
                 }
+                Some(Layer::Tower) => {
+                    let owner = self.world.owners.get(bullet).copied();
+                    self.world.despawn(bullet);
+                    self.tower.hit(position, owner, &mut self.events);
+                }
+                Some(Layer::Shield) => {
+                    self.world.despawn(bullet);
+                    self.tower.hit_shield(position, &mut self.events);
+                }
+                None => (),
+            }
+        }
+    }
+
+    /// What colliding bullets `a` and `b` score: an interception if the player shot down a
+    /// bullet heading for the tower, an annihilation for any other spawned bullet.
+    fn interception(&self, a: Entity, b: Entity) -> Option<ScoreKind> {
+        let owner = |entity| self.world.owners.get(entity).copied();
+        let spawned = match (owner(a), owner(b)) {
+            (Some(Owner::Player), Some(Owner::Spawner)) => b,
+            (Some(Owner::Spawner), Some(Owner::Player)) => a,
+            _ => return None,
+        };
+        let center = self.tower.position().x + self.tower.size().x / 2.0;
+        let position = self.world.center(spawned)?;
+        let Velocity(velocity) = *self.world.velocities.get(spawned)?;
+        Some(if (center - position.x) * velocity.x > 0.0 {
+            ScoreKind::Interception
+        } else {
+            ScoreKind::Annihilation
+        })
+    }
+
+    /// Picks up reloaded assets without restarting the level.
+    pub fn reload(&mut self, assets: &Assets, level: &LevelInfo) {
+        self.texture = assets.texture(&level.art);
+        self.mask = load_mask(assets, level, &self.texture);
+        self.layers = load_layers(assets, level);
+        self.tower.reload(assets, level);
+        self.spawn = level.spawn();
+        self.checkpoints = level.checkpoints();
+        self.checkpoint = self
+            .checkpoint
+            .filter(|&checkpoint| checkpoint < self.checkpoints.len());
+        self.kill_zones = level.kill_zones();
+        self.death_penalty = level.death_penalty;
+
+        let old_bullet_texture =
+            std::mem::replace(&mut self.bullet_texture, assets.texture("Bullet"));
+        let size = rvec2(self.bullet_texture.width, self.bullet_texture.height);
+        let bullets = self.bullets().collect::<Vec<_>>();
+        for bullet in bullets {
+            if let Some(sprite) = self.world.sprites.get_mut(bullet) {
+                if Rc::ptr_eq(&sprite.texture, &old_bullet_texture) {
+                    sprite.texture = self.bullet_texture.clone();
+                }
+            }
+            if let Some(collider) = self.world.colliders.get_mut(bullet) {
+                collider.size = size;
             }
         }
+        self.sync_tower();
     }
 
-    pub(crate) fn draw(&self, d: &mut RaylibMode2D<RaylibDrawHandle>, bullet_texture: &Texture2D) {
-        d.draw_texture(&self.texture, 0, 0, Color::WHITE);
+    fn sync_tower(&mut self) {
+        self.world
+            .positions
+            .insert(self.tower_entity, Position(self.tower.position()));
+        self.world.colliders.insert(
+            self.tower_entity,
+            Collider {
+                size: self.tower.size(),
+                layer: Layer::Tower,
+            },
+        );
+        match (self.tower.shield_rect(), self.shield_entity) {
+            (Some(rect), shield) => {
+                let shield = shield.unwrap_or_else(|| {
+                    let shield = self.world.spawn();
+                    self.world.owners.insert(shield, Owner::Scene);
+                    shield
+                });
+                self.world
+                    .positions
+                    .insert(shield, Position(rvec2(rect.x, rect.y)));
+                self.world.colliders.insert(
+                    shield,
+                    Collider {
+                        size: rvec2(rect.width, rect.height),
+                        layer: Layer::Shield,
+                    },
+                );
+                self.shield_entity = Some(shield);
+            }
+            (None, Some(shield)) => {
+                self.world.despawn(shield);
+                self.shield_entity = None;
+            }
+            (None, None) => (),
+        }
+    }
+
+    /// Parallax layers behind the level art.
+    pub(crate) fn draw_background(&self, d: &mut impl RaylibDraw, camera: Camera2D) {
+        for layer in self.layers.iter().filter(|layer| !layer.foreground()) {
+            layer.draw(d, camera);
+        }
+    }
+
+    /// Parallax layers in front of everything, drawn after the player.
+    pub(crate) fn draw_foreground(&self, d: &mut impl RaylibDraw, camera: Camera2D) {
+        for layer in self.layers.iter().filter(|layer| layer.foreground()) {
+            layer.draw(d, camera);
+        }
+    }
+
+    pub(crate) fn draw(&self, d: &mut impl RaylibDraw) {
+        if let Some(texture) = self.texture.texture() {
+            d.draw_texture(texture, 0, 0, Color::WHITE);
+        }
+        for (i, &checkpoint) in self.checkpoints.iter().enumerate() {
+            let color = if self.checkpoint == Some(i) {
+                Color::LIME
+            } else {
+                Color::GRAY
+            };
+            let top = checkpoint - rvec2(0.0, FLAG_HEIGHT);
+            d.draw_line_v(checkpoint, top, Color::LIGHTGRAY);
+            d.draw_triangle(top, top + rvec2(0, 5), top + rvec2(6, 2.5), color);
+        }
         self.tower.draw(d);
-        for bullet in &self.bullets {
-            d.draw_texture_v(bullet_texture, bullet.position(), Color::WHITE);
+        systems::draw_sprites(&self.world, d);
+    }
+
+    /// Where a player of `size` comes back after dying: standing on the last checkpoint
+    /// reached, or at the level's spawn.
+    pub fn respawn_point(&self, size: Vector2) -> Vector2 {
+        match self.checkpoint {
+            Some(checkpoint) => self.checkpoints[checkpoint] - rvec2(size.x / 2.0, size.y),
+            None => self.spawn,
+        }
+    }
+
+    /// Makes the checkpoint `rect` touches the one to respawn at.
+    pub(crate) fn reach_checkpoint(&mut self, rect: Rectangle) {
+        let reached = self.checkpoints.iter().position(|checkpoint| {
+            rrect(
+                checkpoint.x - 1.0,
+                checkpoint.y - FLAG_HEIGHT,
+                2,
+                FLAG_HEIGHT,
+            )
+            .check_collision_recs(&rect)
+        });
+        if let Some(checkpoint) = reached.filter(|&i| Some(i) != self.checkpoint) {
+            self.checkpoint = Some(checkpoint);
+            self.events.push(GameEvent::CheckpointReached {
+                position: self.checkpoints[checkpoint],
+            });
         }
     }
 
-    pub fn reverse_roles(&mut self, player_center: Vector2, audio: &mut RaylibAudio) {
+    /// The column around the tower the player has to be out of for roles to reverse.
+    pub fn tower_zone(&self) -> Rectangle {
+        let mut rect = self.tower.rect();
+        rect.x -= 10.0;
+        rect.width += 20.0;
+        rect.y = 0.0;
+        rect.height = self.height() as _;
+        rect
+    }
+
+    /// Whether `rect` touches a kill zone, either a configured one or past a kill edge.
+    pub fn kills(&self, rect: Rectangle) -> bool {
+        self.mask.kills_with(rect, &self.kill_zones)
+    }
+
+    pub fn kill_zones(&self) -> &[Rectangle] {
+        &self.kill_zones
+    }
+
+    /// Applies the level's death penalty to the tower.
+    pub(crate) fn player_died(&mut self, position: Vector2) {
+        self.tower.penalize(self.death_penalty);
+        self.events.push(GameEvent::PlayerKilled { position });
+    }
+
+    pub fn reverse_roles(&mut self, player_center: Vector2) {
         self.tower.reverse_roles(
             player_center.x < self.tower.position().x + self.tower.size().x / 2.0,
-            audio,
+            &mut self.events,
         );
+        self.sync_tower();
     }
 
     pub fn width(&self) -> i32 {
@@ -153,11 +381,74 @@ impl Scene {
         rvec2(self.texture.width, self.texture.height)
     }
 
-    pub fn metadata(&self, pos: Vector2) -> Color {
-        self.metadata[pos.x as usize + pos.y as usize * self.width() as usize]
+    pub fn mask(&self) -> &Mask {
+        &self.mask
+    }
+
+    /// Sets one pixel of the collision mask, ignoring positions outside of the scene.
+    pub fn paint(&mut self, pos: Vector2, color: Color) {
+        self.mask
+            .set(pos.x.floor() as i32, pos.y.floor() as i32, color);
+    }
+
+    /// The collision mask as an image, e.g. to save it.
+    pub fn metadata_image(&self) -> Image {
+        self.mask.image()
+    }
+
+    /// Replaces the collision mask, resizing `image` to the scene if needed.
+    pub fn set_metadata_image(&mut self, image: &Image) {
+        let mut image = image.clone();
+        if image.width() != self.width() || image.height() != self.height() {
+            image.resize_nn(self.width(), self.height());
+        }
+        self.mask = Mask::new(&image, self.mask.edges());
+    }
+
+    /// Tints every solid pixel of the collision mask.
+    pub(crate) fn draw_mask(&self, d: &mut impl RaylibDraw, color: Color) {
+        for y in 0..self.height() {
+            let mut run = None;
+            for x in 0..=self.width() {
+                let solid = self.mask.get(x, y) == Some(Color::BLACK);
+                match (solid, run) {
+                    (true, None) => run = Some(x),
+                    (false, Some(start)) => {
+                        d.draw_rectangle(start, y, x - start, 1, color);
+                        run = None;
+                    }
+                    _ => (),
+                }
+            }
+        }
     }
 
     pub fn tower(&self) -> &Tower {
         &self.tower
     }
+
+    pub fn tower_mut(&mut self) -> &mut Tower {
+        &mut self.tower
+    }
+
+    /// Center of the bullet the shield is tracking, if any.
+    pub fn target(&self) -> Option<Vector2> {
+        self.target
+    }
+}
+
+fn load_layers(assets: &Assets, level: &LevelInfo) -> Vec<ParallaxLayer> {
+    level
+        .layers
+        .iter()
+        .map(|layer| ParallaxLayer::new(assets, layer))
+        .collect()
+}
+
+fn load_mask(assets: &Assets, level: &LevelInfo, texture: &Picture) -> Mask {
+    let mut metadata = (*assets.image(&level.metadata)).clone();
+    if metadata.width() != texture.width || metadata.height() != texture.height {
+        metadata.resize_nn(texture.width, texture.height);
+    }
+    Mask::new(&metadata, level.edges)
 }
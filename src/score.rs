@@ -0,0 +1,139 @@
+use crate::events::*;
+
+/// Seconds without scoring before a combo runs out.
+const COMBO_TIME: f32 = 4.0;
+/// Combo length that adds one to the multiplier.
+const COMBO_STEP: u32 = 5;
+const MAX_MULTIPLIER: u32 = 5;
+/// Points for each second survived, never multiplied.
+const SURVIVAL_POINTS: f32 = 10.0;
+/// Extra points for each second left on the reversal timer when the player wins it.
+pub const REVERSAL_TIME_POINTS: f32 = 100.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScoreKind {
+    /// A player bullet shot down a bullet heading for the tower.
+    Interception,
+    /// A player bullet annihilated any other spawned bullet.
+    Annihilation,
+    /// A player bullet hit the tower while roles were reversed.
+    TowerShot,
+    /// The player brought the reversed tower down before its timer ran out.
+    ReversalEnded,
+    Survival,
+}
+
+const SCORE_KINDS: &[(ScoreKind, &str)] = &[
+    (ScoreKind::Interception, "Interceptions"),
+    (ScoreKind::Annihilation, "Annihilations"),
+    (ScoreKind::TowerShot, "Tower shots"),
+    (ScoreKind::ReversalEnded, "Reversals won"),
+    (ScoreKind::Survival, "Survival"),
+];
+
+impl ScoreKind {
+    pub fn name(self) -> &'static str {
+        SCORE_KINDS
+            .iter()
+            .find(|(kind, _)| *kind == self)
+            .map(|(_, name)| *name)
+            .unwrap_or_default()
+    }
+
+    /// Points before the combo multiplier.
+    pub fn points(self) -> u64 {
+        match self {
+            Self::Interception => 100,
+            Self::Annihilation => 50,
+            Self::TowerShot => 25,
+            Self::ReversalEnded => 500,
+            Self::Survival => 0,
+        }
+    }
+
+    fn index(self) -> usize {
+        SCORE_KINDS
+            .iter()
+            .position(|(kind, _)| *kind == self)
+            .unwrap_or_default()
+    }
+}
+
+/// Points earned from one kind of play.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ScoreLine {
+    pub count: u32,
+    pub points: u64,
+}
+
+/// Adds up `Scored` events, chaining them into combos that raise the multiplier. Hits to the
+/// tower and deaths break the combo.
+#[derive(Clone, Debug, Default)]
+pub struct Score {
+    lines: [ScoreLine; SCORE_KINDS.len()],
+    survival: f32,
+    combo: u32,
+    best_combo: u32,
+    combo_timer: f32,
+}
+
+impl Score {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle(&mut self, event: &GameEvent) {
+        match *event {
+            GameEvent::Scored { kind, points, .. } => {
+                self.combo += 1;
+                self.best_combo = self.best_combo.max(self.combo);
+                self.combo_timer = COMBO_TIME;
+                let multiplier = self.multiplier() as u64;
+                let line = &mut self.lines[kind.index()];
+                line.count += 1;
+                line.points += points * multiplier;
+            }
+            GameEvent::TowerHit {
+                reversed: false, ..
+            }
+            | GameEvent::PlayerKilled { .. } => self.combo = 0,
+            _ => (),
+        }
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.survival += dt;
+        let line = &mut self.lines[ScoreKind::Survival.index()];
+        line.count = self.survival as u32;
+        line.points = (self.survival * SURVIVAL_POINTS) as u64;
+
+        self.combo_timer -= dt;
+        if self.combo_timer <= 0.0 {
+            self.combo = 0;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.lines.iter().map(|line| line.points).sum()
+    }
+
+    pub fn combo(&self) -> u32 {
+        self.combo
+    }
+
+    pub fn best_combo(&self) -> u32 {
+        self.best_combo
+    }
+
+    /// What the next points are multiplied by.
+    pub fn multiplier(&self) -> u32 {
+        (1 + self.combo / COMBO_STEP).min(MAX_MULTIPLIER)
+    }
+
+    /// Every kind of play with what it earned, for the Game Over screen.
+    pub fn breakdown(&self) -> impl Iterator<Item = (ScoreKind, ScoreLine)> + '_ {
+        SCORE_KINDS
+            .iter()
+            .map(|(kind, _)| (*kind, self.lines[kind.index()]))
+    }
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::*;
+use crate::viewport::Scaling;
+
+pub const SETTINGS_PATH: &str = "settings.json";
+
+/// Player preferences persisted between runs.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub audio: AudioSettings,
+    pub show_fps: bool,
+    pub scaling: Scaling,
+    pub fullscreen: bool,
+    pub difficulty: Difficulty,
+    /// Adjusts bullets to how well the player is doing.
+    pub adaptive: bool,
+    /// Used by the custom difficulty.
+    pub custom_difficulty: Tuning,
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AudioSettings {
+    pub master: f32,
+    pub music: f32,
+    pub sfx: f32,
+}
+
+impl Default for AudioSettings {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            music: 0.6,
+            sfx: 1.0,
+        }
+    }
+}
+
+impl Settings {
+    /// Missing or unreadable settings fall back to the defaults, and so does an invalid
+    /// custom difficulty.
+    pub fn load() -> Self {
+        let mut settings: Self = std::fs::read(SETTINGS_PATH)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())
+            .unwrap_or_default();
+        if let Err(err) = settings.custom_difficulty.check() {
+            eprintln!(
+                "Ignoring the custom difficulty in {}: {}",
+                SETTINGS_PATH, err
+            );
+            settings.custom_difficulty = Tuning::default();
+        }
+        settings
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|err| err.to_string())?;
+        std::fs::write(SETTINGS_PATH, json).map_err(|err| err.to_string())
+    }
+}
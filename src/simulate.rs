@@ -0,0 +1,377 @@
+use std::io::Write;
+
+use serde::{Deserialize, Serialize};
+
+use crate::bot::*;
+use crate::difficulty::*;
+use crate::events::*;
+use crate::game::*;
+use crate::player::*;
+use crate::resources::*;
+use crate::settings::*;
+
+const USAGE: &str = "Usage: personality simulate [--level name] [--runs n] [--seeds 1,2,3|1-100] \
+[--bot strategy | --inputs file] [--difficulty name] [--adaptive] [--format csv|json] [--output file] \
+[--bullet-interval min-max] [--bullet-speed min-max] [--reversal-countdown min-max]";
+
+/// One step of player input with the frame time and scale it was played at, as written by
+/// `personality record` and replayed by `personality simulate --inputs`.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct RecordedInput {
+    pub direction: f32,
+    pub jump: bool,
+    pub release_jump: bool,
+    pub shoot: bool,
+    pub aim: [f32; 2],
+    #[serde(default = "default_dt")]
+    pub dt: f32,
+    #[serde(default = "default_scale")]
+    pub scale: f32,
+}
+
+fn default_dt() -> f32 {
+    DT
+}
+
+fn default_scale() -> f32 {
+    SCALE
+}
+
+impl From<GameStep> for RecordedInput {
+    fn from(step: GameStep) -> Self {
+        let input = step.input;
+        Self {
+            direction: input.direction,
+            jump: input.jump,
+            release_jump: input.release_jump,
+            shoot: input.shoot,
+            aim: [input.aim.x, input.aim.y],
+            dt: step.dt,
+            scale: step.scale,
+        }
+    }
+}
+
+impl From<RecordedInput> for GameStep {
+    fn from(input: RecordedInput) -> Self {
+        Self {
+            input: PlayerInput {
+                direction: input.direction,
+                jump: input.jump,
+                release_jump: input.release_jump,
+                shoot: input.shoot,
+                aim: rvec2(input.aim[0], input.aim[1]),
+            },
+            dt: input.dt,
+            scale: input.scale,
+        }
+    }
+}
+
+/// What happened during one simulated run.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RunStats {
+    pub seed: u32,
+    pub survival_time: f32,
+    pub tower_hits: u32,
+    pub reversals: u32,
+    pub reversals_won: u32,
+    pub bullets_annihilated: u32,
+    pub deaths: u32,
+}
+
+impl RunStats {
+    fn handle(&mut self, event: &GameEvent) {
+        match event {
+            GameEvent::TowerHit {
+                reversed: false, ..
+            } => self.tower_hits += 1,
+            GameEvent::RolesReversed { .. } => self.reversals += 1,
+            GameEvent::RolesRestored => self.reversals_won += 1,
+            GameEvent::BulletsAnnihilated { .. } => self.bullets_annihilated += 1,
+            GameEvent::PlayerKilled { .. } => self.deaths += 1,
+            _ => (),
+        }
+    }
+}
+
+/// Averages over every run of one level.
+#[derive(Clone, Debug, Serialize)]
+pub struct Summary {
+    pub runs: usize,
+    pub mean_survival_time: f32,
+    pub median_survival_time: f32,
+    pub mean_tower_hits: f32,
+    pub mean_reversals: f32,
+    /// Share of reversals the player won, over every run.
+    pub reversal_win_rate: f32,
+    pub mean_bullets_annihilated: f32,
+    pub mean_deaths: f32,
+}
+
+impl Summary {
+    fn new(runs: &[RunStats]) -> Self {
+        let count = runs.len().max(1) as f32;
+        let mean = |value: fn(&RunStats) -> f32| runs.iter().map(value).sum::<f32>() / count;
+        let mut times = runs.iter().map(|run| run.survival_time).collect::<Vec<_>>();
+        times.sort_by(f32::total_cmp);
+        let reversals = runs.iter().map(|run| run.reversals).sum::<u32>();
+        let won = runs.iter().map(|run| run.reversals_won).sum::<u32>();
+        Self {
+            runs: runs.len(),
+            mean_survival_time: mean(|run| run.survival_time),
+            median_survival_time: times.get(times.len() / 2).copied().unwrap_or_default(),
+            mean_tower_hits: mean(|run| run.tower_hits as f32),
+            mean_reversals: mean(|run| run.reversals as f32),
+            reversal_win_rate: if reversals > 0 {
+                won as f32 / reversals as f32
+            } else {
+                0.0
+            },
+            mean_bullets_annihilated: mean(|run| run.bullets_annihilated as f32),
+            mean_deaths: mean(|run| run.deaths as f32),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LevelReport {
+    level: String,
+    difficulty: Difficulty,
+    adaptive: bool,
+    tuning: Tuning,
+    summary: Summary,
+    runs: Vec<RunStats>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Json,
+}
+
+/// Runs `personality simulate`, playing every seed as fast as possible and writing the
+/// statistics of each run.
+pub fn run(args: &[String]) -> bool {
+    let mut level = None;
+    let mut runs = 100;
+    let mut seeds = None;
+    let mut strategy = Strategy::Balanced;
+    // Replayed instead of letting the bot play, when given.
+    let mut inputs: Option<Vec<GameStep>> = None;
+    let mut format = Format::Csv;
+    let mut output = None;
+    let mut difficulty = Difficulty::Normal;
+    let mut adaptive = false;
+    let mut bullet_interval = None;
+    let mut bullet_speed = None;
+    let mut reversal_countdown = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "--adaptive" {
+            adaptive = true;
+            continue;
+        }
+        let value = match args.next() {
+            Some(value) => value,
+            None => {
+                eprintln!("{}", USAGE);
+                return false;
+            }
+        };
+        let parsed = match arg.as_str() {
+            "--level" => {
+                level = Some(value.clone());
+                Ok(())
+            }
+            "--runs" => value
+                .parse()
+                .ok()
+                .filter(|&runs| runs > 0)
+                .map(|value| runs = value)
+                .ok_or_else(|| format!("Invalid run count {}", value)),
+            "--seeds" => parse_seeds(value).map(|value| seeds = Some(value)),
+            "--bot" => Strategy::parse(value)
+                .map(|value| strategy = value)
+                .ok_or_else(|| format!("Unknown strategy {}", value)),
+            "--inputs" => read_inputs(value).map(|value| inputs = Some(value)),
+            "--difficulty" => Difficulty::parse(value)
+                .map(|value| difficulty = value)
+                .ok_or_else(|| format!("Unknown difficulty {}", value)),
+            "--format" => match value.as_str() {
+                "csv" => Ok(Format::Csv),
+                "json" => Ok(Format::Json),
+                _ => Err(format!("Unknown format {}", value)),
+            }
+            .map(|value| format = value),
+            "--output" => {
+                output = Some(value.clone());
+                Ok(())
+            }
+            "--bullet-interval" => parse_range(value).map(|range| bullet_interval = Some(range)),
+            "--bullet-speed" => parse_range(value).map(|range| bullet_speed = Some(range)),
+            "--reversal-countdown" => {
+                parse_range(value).map(|range| reversal_countdown = Some(range))
+            }
+            _ => Err(USAGE.to_owned()),
+        };
+        if let Err(err) = parsed {
+            eprintln!("{}", err);
+            return false;
+        }
+    }
+    let seeds = seeds.unwrap_or_else(|| (1..=runs).collect());
+    let mut tuning = difficulty.tuning(&Settings::load().custom_difficulty);
+    tuning.bullet_interval = bullet_interval.unwrap_or(tuning.bullet_interval);
+    tuning.bullet_speed = bullet_speed.unwrap_or(tuning.bullet_speed);
+    tuning.reversal_countdown = reversal_countdown.unwrap_or(tuning.reversal_countdown);
+
+    let (assets, levels) = match load_headless(level.as_deref()) {
+        Some(loaded) => loaded,
+        None => return false,
+    };
+
+    let mut reports = Vec::new();
+    for level in &levels {
+        let runs = seeds
+            .iter()
+            .map(|&seed| {
+                let mut stats = RunStats {
+                    seed,
+                    ..Default::default()
+                };
+                let mut bot = inputs.is_none().then(|| Bot::new(strategy));
+                let mut replay = inputs.iter().flatten();
+                stats.survival_time = play_headless(
+                    &assets,
+                    level,
+                    tuning,
+                    adaptive,
+                    seed as u64,
+                    |game| match &mut bot {
+                        Some(bot) => GameStep::new(bot.input(DT, &game.scene, &game.player)),
+                        None => replay
+                            .next()
+                            .copied()
+                            .unwrap_or_else(|| GameStep::new(PlayerInput::default())),
+                    },
+                    |event| stats.handle(event),
+                );
+                stats
+            })
+            .collect::<Vec<_>>();
+
+        let summary = Summary::new(&runs);
+        eprintln!(
+            "Level {}: {} runs, mean survival {:.1}s (median {:.1}s), {:.1} tower hits, \
+{:.2} reversals ({:.0}% won), {:.1} bullets annihilated",
+            level.name,
+            summary.runs,
+            summary.mean_survival_time,
+            summary.median_survival_time,
+            summary.mean_tower_hits,
+            summary.mean_reversals,
+            summary.reversal_win_rate * 100.0,
+            summary.mean_bullets_annihilated
+        );
+        reports.push(LevelReport {
+            level: level.name.clone(),
+            difficulty,
+            adaptive,
+            tuning,
+            summary,
+            runs,
+        });
+    }
+
+    let text = match format {
+        Format::Csv => csv(&reports),
+        Format::Json => match serde_json::to_string_pretty(&reports) {
+            Ok(json) => json + "\n",
+            Err(err) => {
+                eprintln!("Failed to write the report: {}", err);
+                return false;
+            }
+        },
+    };
+    let written = match &output {
+        Some(path) => std::fs::write(path, text),
+        None => std::io::stdout().write_all(text.as_bytes()),
+    };
+    if let Err(err) = written {
+        eprintln!("Failed to write the report: {}", err);
+        return false;
+    }
+    true
+}
+
+/// One row per run, for spreadsheets.
+fn csv(reports: &[LevelReport]) -> String {
+    let mut csv = "level,seed,survival_time,tower_hits,reversals,reversals_won,\
+bullets_annihilated,deaths\n"
+        .to_owned();
+    for report in reports {
+        for run in &report.runs {
+            csv += &format!(
+                "{},{},{:.3},{},{},{},{},{}\n",
+                report.level,
+                run.seed,
+                run.survival_time,
+                run.tower_hits,
+                run.reversals,
+                run.reversals_won,
+                run.bullets_annihilated,
+                run.deaths
+            );
+        }
+    }
+    csv
+}
+
+/// Parses a list of seeds like `1,5,9` or an inclusive range like `1-100`.
+fn parse_seeds(value: &str) -> Result<Vec<u32>, String> {
+    let invalid = || format!("Invalid seeds {}", value);
+    if let Some((start, end)) = value.split_once('-') {
+        let start = start.parse::<u32>().map_err(|_| invalid())?;
+        let end = end.parse::<u32>().map_err(|_| invalid())?;
+        if start > end {
+            return Err(invalid());
+        }
+        return Ok((start..=end).collect());
+    }
+    value
+        .split(',')
+        .map(|seed| seed.parse().map_err(|_| invalid()))
+        .collect()
+}
+
+/// Parses a range like `1-2.5` into `[min, max]`.
+fn parse_range(value: &str) -> Result<[f32; 2], String> {
+    let invalid = || format!("Invalid range {}", value);
+    let (min, max) = value.split_once('-').ok_or_else(invalid)?;
+    let min = min.parse::<f32>().map_err(|_| invalid())?;
+    let max = max.parse::<f32>().map_err(|_| invalid())?;
+    check_range([min, max]).map_err(|_| invalid())?;
+    Ok([min, max])
+}
+
+/// Appends one step to a recording.
+pub fn record(writer: &mut impl Write, step: GameStep) -> Result<(), String> {
+    let line = serde_json::to_string(&RecordedInput::from(step)).map_err(|err| err.to_string())?;
+    writeln!(writer, "{}", line).map_err(|err| err.to_string())
+}
+
+/// Reads inputs recorded by `personality record`, one JSON object per line.
+fn read_inputs(path: &str) -> Result<Vec<GameStep>, String> {
+    let text =
+        std::fs::read_to_string(path).map_err(|err| format!("Failed to read {}: {}", path, err))?;
+    text.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            serde_json::from_str::<RecordedInput>(line)
+                .map(GameStep::from)
+                .map_err(|err| format!("Invalid input in {}: {}", path, err))
+        })
+        .collect()
+}
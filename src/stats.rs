@@ -0,0 +1,207 @@
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::*;
+use crate::ecs::Owner;
+use crate::events::*;
+use crate::score::*;
+use crate::tower::*;
+
+/// Every finished run is appended here as one JSON object per line.
+pub const STATS_PATH: &str = "stats.jsonl";
+
+/// What happened during one run, counted from gameplay events.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    /// Seconds since the Unix epoch when the run ended.
+    pub finished_at: u64,
+    pub level: String,
+    pub difficulty: Difficulty,
+    pub adaptive: bool,
+    /// Whether the bot played any part of the run.
+    pub autoplayed: bool,
+    pub score: u64,
+    pub best_combo: u32,
+    pub play_time: f32,
+    pub shots_fired: u32,
+    /// Shots that intercepted or annihilated a bullet or hit the reversed tower.
+    pub shots_hit: u32,
+    pub jumps: u32,
+    pub tower_hits_left: u32,
+    pub tower_hits_right: u32,
+    pub reversals: u32,
+    pub reversals_won: u32,
+    pub deaths: u32,
+    /// Seconds the tower spent in [`TowerState::Normal`].
+    pub normal_time: f32,
+    /// Seconds the tower spent in [`TowerState::Reversed`].
+    pub reversed_time: f32,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle(&mut self, event: &GameEvent, tower: &Tower) {
+        match *event {
+            GameEvent::BulletFired {
+                owner: Owner::Player,
+                ..
+            } => self.shots_fired += 1,
+            GameEvent::Scored {
+                kind: ScoreKind::Interception | ScoreKind::Annihilation | ScoreKind::TowerShot,
+                ..
+            } => self.shots_hit += 1,
+            GameEvent::PlayerJumped { .. } => self.jumps += 1,
+            GameEvent::TowerHit {
+                position,
+                reversed: false,
+            } => {
+                if position.x < tower.position().x + tower.size().x / 2.0 {
+                    self.tower_hits_left += 1;
+                } else {
+                    self.tower_hits_right += 1;
+                }
+            }
+            GameEvent::RolesReversed { .. } => self.reversals += 1,
+            GameEvent::RolesRestored => self.reversals_won += 1,
+            GameEvent::PlayerKilled { .. } => self.deaths += 1,
+            _ => (),
+        }
+    }
+
+    pub fn update(&mut self, dt: f32, tower: &Tower) {
+        self.play_time += dt;
+        if tower.reversed() {
+            self.reversed_time += dt;
+        } else {
+            self.normal_time += dt;
+        }
+    }
+
+    /// Share of shots that hit something worth points.
+    pub fn accuracy(&self) -> f32 {
+        if self.shots_fired > 0 {
+            self.shots_hit as f32 / self.shots_fired as f32
+        } else {
+            0.0
+        }
+    }
+
+    /// Fills in what the events don't tell, once the run is over.
+    pub fn finish(&mut self, level: &str, difficulty: Difficulty, adaptive: bool, score: &Score) {
+        self.finished_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        self.level = level.to_owned();
+        self.difficulty = difficulty;
+        self.adaptive = adaptive;
+        self.score = score.total();
+        self.best_combo = score.best_combo();
+    }
+
+    /// Appends the run to [`STATS_PATH`].
+    pub fn append(&self) -> Result<(), String> {
+        let line = serde_json::to_string(self).map_err(|err| err.to_string())?;
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(STATS_PATH)
+            .map_err(|err| err.to_string())?;
+        writeln!(file, "{}", line).map_err(|err| err.to_string())
+    }
+}
+
+/// Totals over every run in the log that the bot had no part in, for the stats screen.
+#[derive(Clone, Debug, Default)]
+pub struct Lifetime {
+    pub runs: u32,
+    pub best_score: u64,
+    pub best_time: f32,
+    pub totals: Stats,
+}
+
+impl Lifetime {
+    /// Reads [`STATS_PATH`], skipping lines it can't parse. A missing log counts as no runs.
+    pub fn load() -> Self {
+        let mut lifetime = Self::default();
+        let text = std::fs::read_to_string(STATS_PATH).unwrap_or_default();
+        for stats in text
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Stats>(line).ok())
+        {
+            if !stats.autoplayed {
+                lifetime.add(&stats);
+            }
+        }
+        lifetime
+    }
+
+    fn add(&mut self, stats: &Stats) {
+        self.runs += 1;
+        self.best_score = self.best_score.max(stats.score);
+        self.best_time = self.best_time.max(stats.play_time);
+
+        let totals = &mut self.totals;
+        totals.score += stats.score;
+        totals.best_combo = totals.best_combo.max(stats.best_combo);
+        totals.play_time += stats.play_time;
+        totals.shots_fired += stats.shots_fired;
+        totals.shots_hit += stats.shots_hit;
+        totals.jumps += stats.jumps;
+        totals.tower_hits_left += stats.tower_hits_left;
+        totals.tower_hits_right += stats.tower_hits_right;
+        totals.reversals += stats.reversals;
+        totals.reversals_won += stats.reversals_won;
+        totals.deaths += stats.deaths;
+        totals.normal_time += stats.normal_time;
+        totals.reversed_time += stats.reversed_time;
+    }
+
+    /// One line of text per statistic.
+    pub fn lines(&self) -> Vec<String> {
+        let totals = &self.totals;
+        vec![
+            format!("Runs: {}", self.runs),
+            format!("Best score: {}", self.best_score),
+            format!("Best time: {}", duration(self.best_time)),
+            format!("Best combo: {}", totals.best_combo),
+            format!("Time played: {}", duration(totals.play_time)),
+            format!(
+                "Shots: {} fired, {:.0}% hit",
+                totals.shots_fired,
+                totals.accuracy() * 100.0
+            ),
+            format!("Jumps: {}", totals.jumps),
+            format!(
+                "Tower hits: {} left, {} right",
+                totals.tower_hits_left, totals.tower_hits_right
+            ),
+            format!(
+                "Reversals: {}, {} won",
+                totals.reversals, totals.reversals_won
+            ),
+            format!("Deaths: {}", totals.deaths),
+            format!(
+                "Tower time: {} normal, {} reversed",
+                duration(totals.normal_time),
+                duration(totals.reversed_time)
+            ),
+        ]
+    }
+}
+
+fn duration(seconds: f32) -> String {
+    let seconds = seconds as u32;
+    format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
@@ -1,4 +1,12 @@
+use std::rc::Rc;
+
+use crate::assets::*;
+use crate::difficulty::*;
+use crate::ecs::Owner;
+use crate::events::*;
+use crate::manifest::*;
 use crate::resources::*;
+use crate::score::*;
 
 pub enum TowerState {
     Normal,
@@ -11,114 +19,91 @@ pub enum TowerState {
 
 pub struct Tower {
     position: Vector2,
-    textures: Animation,
-    shield_textures: Animation,
-    hit_sound: Sound,
-    roles_reversed_sound: Sound,
+    textures: Rc<Animation>,
+    shield_textures: Rc<Animation>,
 
     health: f32,
-    damaged: f32,
+    animation: AnimationPlayer,
     state: TowerState,
+
+    damage: f32,
+    reversed_damage: f32,
+    reversal_time: f32,
 }
 
 impl Tower {
-    pub fn new(
-        rl: &mut RaylibHandle,
-        thread: &RaylibThread,
-        position: Vector2,
-        index: usize,
-    ) -> Self {
-        let textures = Animation::load(rl, thread, &format!("Assets/Tower{}-", index));
-        let shield_textures = Animation::load(rl, thread, &format!("Assets/Shield{}-", index));
+    pub fn new(assets: &Assets, level: &LevelInfo) -> Self {
         Self {
-            position,
-            textures,
-            shield_textures,
-            hit_sound: Sound::load_sound("Assets/Hit.wav").expect("Failed to load hit sound."),
-            roles_reversed_sound: Sound::load_sound("Assets/RolesReversed.wav")
-                .expect("Failed to load roles reversed sound."),
+            position: level.tower_position(),
+            textures: assets.animation(&level.tower),
+            shield_textures: assets.animation(&level.shield),
 
             health: 1.0,
-            damaged: 0.0,
+            animation: AnimationPlayer::new("normal"),
             state: TowerState::Normal,
+
+            damage: 1.0 / 100.0,
+            reversed_damage: 1.0 / 10.0,
+            reversal_time: 10.0,
         }
     }
 
-    pub(crate) fn update(
-        &mut self,
-        rl: &mut RaylibHandle,
-        bullet: Option<Vector2>,
-        game_over: &mut bool,
-    ) {
-        self.damaged = (self.damaged - rl.get_frame_time()).max(0.0);
+    /// Takes the damage and reversal time from a difficulty.
+    pub fn tune(&mut self, tuning: &Tuning) {
+        self.damage = tuning.tower_damage;
+        self.reversed_damage = tuning.reversed_damage;
+        self.reversal_time = tuning.reversal_time;
+    }
+
+    pub fn reload(&mut self, assets: &Assets, level: &LevelInfo) {
+        self.position = level.tower_position();
+        self.textures = assets.animation(&level.tower);
+        self.shield_textures = assets.animation(&level.shield);
+    }
+
+    pub(crate) fn update(&mut self, dt: f32, bullet: Option<Vector2>, events: &mut Events) {
+        self.animation.update(dt, &self.textures);
         if let TowerState::Reversed {
             shield,
             bad_health,
             timer,
         } = &mut self.state
         {
-            *timer -= rl.get_frame_time();
+            *timer -= dt;
             if *timer <= 0.0 {
-                *game_over = true;
+                events.push(GameEvent::GameOver);
             }
 
-            shield.update(rl, bullet, &self.shield_textures);
+            shield.update(dt, bullet, &self.shield_textures);
             if *bad_health <= 0.0 {
+                let time_left = timer.max(0.0);
                 self.state = TowerState::Normal;
+                events.push(GameEvent::RolesRestored);
+                events.push(GameEvent::Scored {
+                    kind: ScoreKind::ReversalEnded,
+                    points: ScoreKind::ReversalEnded.points()
+                        + (time_left * REVERSAL_TIME_POINTS) as u64,
+                    position: self.position + self.size() / 2.0,
+                });
             }
         } else if self.health <= 0.0 {
-            *game_over = true;
+            events.push(GameEvent::GameOver);
         }
     }
 
-    pub(crate) fn draw(&self, d: &mut RaylibMode2D<RaylibDrawHandle>) {
-        let health_bar_size = rvec2(20, 4);
-        let health_bar_pos = self.position
-            + rvec2(
-                (self.textures.width() - health_bar_size.x as i32) / 2,
-                -3 - health_bar_size.y as i32,
-            );
-
-        d.draw_texture_v(
-            &self.textures[(self.damaged > 0.0) as usize],
+    pub(crate) fn draw(&self, d: &mut impl RaylibDraw) {
+        self.textures.draw(
+            d,
+            self.animation.frame(&self.textures),
             self.position,
-            Color::WHITE,
+            false,
         );
-
-        let mut health_color = Color::RED;
-        let mut health = self.health;
-        let mut clock = None;
-        if let TowerState::Reversed {
-            shield,
-            bad_health,
-            timer,
-        } = &self.state
-        {
+        if let TowerState::Reversed { shield, .. } = &self.state {
             shield.draw(d, &self.shield_textures);
-            health_color = Color::BLUE;
-            health = *bad_health;
-            clock = Some(timer);
-        }
-
-        d.draw_rectangle_v(health_bar_pos, health_bar_size, Color::BLACK);
-        d.draw_rectangle_v(health_bar_pos + 1.0, health_bar_size - 2.0, Color::GRAY);
-        d.draw_rectangle_v(
-            health_bar_pos + 1.0,
-            (health_bar_size - 2.0) * rvec2(health, 1),
-            health_color,
-        );
-        if let Some(clock) = clock {
-            d.draw_text(
-                &format!("{}", *clock as i32),
-                health_bar_pos.x as i32,
-                health_bar_pos.y as i32 - 10,
-                10,
-                Color::RED,
-            );
         }
     }
 
-    pub fn reverse_roles(&mut self, flip: bool, audio: &mut RaylibAudio) {
+    pub fn reverse_roles(&mut self, flip: bool, events: &mut Events) {
         self.state = TowerState::Reversed {
             shield: Shield::new(
                 self.position()
@@ -129,24 +114,38 @@ impl Tower {
                 flip,
             ),
             bad_health: 1.0,
-            timer: 10.0,
+            timer: self.reversal_time,
         };
-        audio.play_sound(&self.roles_reversed_sound);
+        events.push(GameEvent::RolesReversed {
+            flip,
+            position: self.position + self.size() / 2.0,
+        });
     }
 
-    pub fn hit(&mut self, audio: &mut RaylibAudio) {
+    pub fn hit(&mut self, position: Vector2, owner: Option<Owner>, events: &mut Events) {
         if let TowerState::Reversed { bad_health, .. } = &mut self.state {
-            *bad_health -= 1.0 / 10.0;
+            *bad_health -= self.reversed_damage;
+            if owner == Some(Owner::Player) {
+                events.push(GameEvent::Scored {
+                    kind: ScoreKind::TowerShot,
+                    points: ScoreKind::TowerShot.points(),
+                    position,
+                });
+            }
         } else {
-            self.health -= 1.0 / 100.0;
+            self.health -= self.damage;
         }
-        self.damaged = 0.1;
-        audio.play_sound(&self.hit_sound);
+        self.animation.trigger("damaged");
+        events.push(GameEvent::TowerHit {
+            position,
+            reversed: self.reversed(),
+        });
     }
 
-    pub fn hit_shield(&mut self) {
+    pub fn hit_shield(&mut self, position: Vector2, events: &mut Events) {
         if let TowerState::Reversed { shield, .. } = &mut self.state {
-            shield.damaged = 0.1;
+            shield.animation.trigger("damaged");
+            events.push(GameEvent::ShieldHit { position });
         }
     }
 
@@ -183,16 +182,52 @@ impl Tower {
         }
     }
 
+    pub fn health(&self) -> f32 {
+        self.health
+    }
+
+    /// Takes `amount` of health away without counting as a hit, e.g. when the player dies.
+    pub fn penalize(&mut self, amount: f32) {
+        self.health = (self.health - amount).max(0.0);
+    }
+
+    pub fn set_health(&mut self, health: f32) {
+        self.health = health.clamp(0.0, 1.0);
+    }
+
+    /// Health of the reversed tower the player is attacking, if roles are reversed.
+    pub fn bad_health(&self) -> Option<f32> {
+        match &self.state {
+            TowerState::Reversed { bad_health, .. } => Some(*bad_health),
+            TowerState::Normal => None,
+        }
+    }
+
+    /// Seconds left to bring the reversed tower down.
+    pub fn reversal_timer(&self) -> Option<f32> {
+        match &self.state {
+            TowerState::Reversed { timer, .. } => Some(*timer),
+            TowerState::Normal => None,
+        }
+    }
+
     pub fn reversed(&self) -> bool {
         matches!(self.state, TowerState::Reversed { .. })
     }
+
+    pub fn shield(&self) -> Option<&Shield> {
+        match &self.state {
+            TowerState::Reversed { shield, .. } => Some(shield),
+            TowerState::Normal => None,
+        }
+    }
 }
 
 pub struct Shield {
     position: Vector2,
     target: f32,
     flip: bool,
-    damaged: f32,
+    animation: AnimationPlayer,
 }
 
 impl Shield {
@@ -201,38 +236,28 @@ impl Shield {
             position,
             target: position.y,
             flip,
-            damaged: 0.0,
+            animation: AnimationPlayer::new("normal"),
         }
     }
 
-    pub(self) fn update(
-        &mut self,
-        rl: &mut RaylibHandle,
-        bullet: Option<Vector2>,
-        shield_textures: &Animation,
-    ) {
+    pub(self) fn update(&mut self, dt: f32, bullet: Option<Vector2>, shield_textures: &Animation) {
         let mut time = 0.7;
 
-        self.damaged = (self.damaged - rl.get_frame_time()).max(0.0);
+        self.animation.update(dt, shield_textures);
         if let Some(bullet) = bullet {
             self.target = bullet.y - shield_textures.height() as f32 / 2.0;
             time -= (self.position.x + shield_textures.size().x / 2.0 - bullet.x).abs() / 200.0;
         }
 
-        self.position.y += (self.target - self.position.y) * rl.get_frame_time() / time;
+        self.position.y += (self.target - self.position.y) * dt / time;
     }
 
-    pub(self) fn draw(&self, d: &mut RaylibMode2D<RaylibDrawHandle>, shield_textures: &Animation) {
-        d.draw_texture_rec(
-            &shield_textures[(self.damaged > 0.0) as usize],
-            rrect(
-                0,
-                0,
-                shield_textures.width() * if self.flip { -1 } else { 1 },
-                shield_textures.height(),
-            ),
+    pub(self) fn draw(&self, d: &mut impl RaylibDraw, shield_textures: &Animation) {
+        shield_textures.draw(
+            d,
+            self.animation.frame(shield_textures),
             self.position,
-            Color::WHITE,
+            self.flip,
         );
     }
 
@@ -240,6 +265,11 @@ impl Shield {
         self.position
     }
 
+    /// Height the shield is moving its top edge towards.
+    pub fn target(&self) -> f32 {
+        self.target
+    }
+
     pub fn rect(&self, shield_textures: &Animation) -> Rectangle {
         rrect(
             self.position.x,
@@ -0,0 +1,413 @@
+use std::collections::{HashSet, VecDeque};
+
+use crate::archive::*;
+use crate::assets::*;
+use crate::game::*;
+use crate::manifest::*;
+use crate::mask::*;
+use crate::player::*;
+use crate::resources::*;
+
+/// Longest a simulated move may take before it is given up on.
+const MAX_MOVE_TIME: f32 = 3.0;
+/// How long the movement key is held during a simulated move.
+const HOLD_TIMES: &[f32] = &[0.1, 0.3, 0.6, MAX_MOVE_TIME];
+/// Standing positions closer than this are treated as the same place.
+const GRID: f32 = 2.0;
+
+/// Checks `level` without opening a window, returning every problem found.
+pub fn validate(source: &AssetSource, manifest: &Manifest, level: &LevelInfo) -> Vec<String> {
+    let load = |kind: &str, name: &str, path: Option<&String>| {
+        let path = path.ok_or_else(|| format!("{} {} is not in the manifest", kind, name))?;
+        load_image(source, path).map_err(|err| format!("{} {}: {}", kind, name, err))
+    };
+    let size = |name: &str| {
+        let info = manifest
+            .animations
+            .get(name)
+            .ok_or_else(|| format!("animation {} is not in the manifest", name))?;
+        source
+            .read(&info.atlas)
+            .and_then(|atlas| atlas_frame_size(&atlas))
+            .map_err(|err| format!("animation {}: {}", name, err))
+    };
+
+    match (
+        load("texture", &level.art, manifest.textures.get(&level.art)),
+        load(
+            "image",
+            &level.metadata,
+            manifest.images.get(&level.metadata),
+        ),
+        size(&level.tower),
+        size("Player"),
+    ) {
+        (Ok(art), Ok(metadata), Ok(tower), Ok(player)) => {
+            check(level, &art, metadata, tower, player)
+        }
+        (art, metadata, tower, player) => [art.err(), metadata.err(), tower.err(), player.err()]
+            .into_iter()
+            .flatten()
+            .collect(),
+    }
+}
+
+/// Checks `level` against its art, collision mask and the size of the tower and player.
+fn check(
+    level: &LevelInfo,
+    art: &Image,
+    mut metadata: Image,
+    tower: Vector2,
+    player: Vector2,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    // * Images
+    if art.width() != metadata.width() || art.height() != metadata.height() {
+        problems.push(format!(
+            "metadata {} is {}x{} but the art {} is {}x{}",
+            level.metadata,
+            metadata.width(),
+            metadata.height(),
+            level.art,
+            art.width(),
+            art.height()
+        ));
+        metadata.resize_nn(art.width(), art.height());
+    }
+
+    let width = art.width();
+    let height = art.height();
+    let terrain = Terrain {
+        mask: Mask::new(&metadata, level.edges),
+        kill_zones: level.kill_zones(),
+    };
+    let solid = |rect: Rectangle| terrain.mask.solid(rect);
+    let inside = |rect: Rectangle| {
+        rect.x >= 0.0
+            && rect.y >= 0.0
+            && rect.x + rect.width <= width as f32
+            && rect.y + rect.height <= height as f32
+    };
+
+    // * Tower
+    let position = level.tower_position();
+    let tower = rrect(position.x, position.y, tower.x, tower.y);
+    if !inside(tower) {
+        problems.push(format!(
+            "tower at {:?} does not fit in the scene",
+            level.tower_position
+        ));
+    } else if solid(tower) {
+        problems.push(format!(
+            "tower at {:?} overlaps the terrain",
+            level.tower_position
+        ));
+    }
+
+    // * Lanes
+    for lane in &level.lanes {
+        if lane.top >= lane.bottom || lane.top < 0.0 || lane.bottom > height as f32 {
+            problems.push(format!(
+                "{:?} lane from {} to {} is empty or outside of the scene",
+                lane.side, lane.top, lane.bottom
+            ));
+        }
+    }
+
+    // * Spawn
+    let spawn = level.spawn();
+    let body = Body::new(rrect(spawn.x, spawn.y, player.x, player.y));
+    if !inside(body.rect) {
+        problems.push(format!(
+            "player spawn {:?} is outside of the scene",
+            level.spawn
+        ));
+        return problems;
+    }
+    let zone = rrect(tower.x - 10.0, 0, tower.width + 20.0, height);
+    problems.extend(check_start("spawn", body, zone, &terrain));
+
+    // * Checkpoints
+    for &[x, y] in &level.checkpoints {
+        let body = Body::new(rrect(x - player.x / 2.0, y - player.y, player.x, player.y));
+        if !inside(body.rect) {
+            problems.push(format!("checkpoint {:?} is outside of the scene", [x, y]));
+        } else {
+            problems.extend(check_start(
+                &format!("checkpoint {:?}", [x, y]),
+                body,
+                zone,
+                &terrain,
+            ));
+        }
+    }
+    problems
+}
+
+/// Checks that a player placed as `body` is free, lands safely and can reach `zone`.
+fn check_start(name: &str, body: Body, zone: Rectangle, terrain: &Terrain) -> Option<String> {
+    if terrain.mask.solid(body.rect) {
+        return Some(format!("the {} is inside the terrain", name));
+    }
+    if terrain.kills(body.rect) {
+        return Some(format!("the {} is inside a kill zone", name));
+    }
+    let start = match settle(body, terrain) {
+        Some(start) => start,
+        None => {
+            return Some(format!(
+                "the player falls out of the scene or into a kill zone from the {}",
+                name
+            ))
+        }
+    };
+    if !reachable(start, zone, terrain) {
+        return Some(format!("the tower can't be reached from the {}", name));
+    }
+    None
+}
+
+/// Everything the player can collide with or die in.
+struct Terrain {
+    mask: Mask,
+    kill_zones: Vec<Rectangle>,
+}
+
+impl Terrain {
+    fn kills(&self, rect: Rectangle) -> bool {
+        self.mask.kills_with(rect, &self.kill_zones)
+    }
+}
+
+/// Lets the body fall until it stands on something, or `None` if it falls out of the scene
+/// or into a kill zone.
+fn settle(mut body: Body, terrain: &Terrain) -> Option<Body> {
+    let mut time = 0.0;
+    while time < MAX_MOVE_TIME {
+        if !advance(&mut body, &PlayerInput::default(), terrain) {
+            return None;
+        }
+        if body.grounded {
+            return Some(body);
+        }
+        time += DT;
+    }
+    None
+}
+
+/// Steps the body like the game does, returning false once it is lost.
+fn advance(body: &mut Body, input: &PlayerInput, terrain: &Terrain) -> bool {
+    let mask = &terrain.mask;
+    let bounds = rrect(0, 0, mask.width(), mask.height());
+    body.step(input, DT, SCALE, bounds, &|rect| mask.solid(rect));
+    let position = mask.wrap(body.rect);
+    body.rect.x = position.x;
+    body.rect.y = position.y;
+    !terrain.kills(body.rect) && !mask.fell_out(body.rect)
+}
+
+/// Searches every place the player can get to by walking and jumping from `start`, until
+/// one of them touches `zone`.
+fn reachable(start: Body, zone: Rectangle, terrain: &Terrain) -> bool {
+    let key = |body: &Body| {
+        (
+            (body.rect.x / GRID).round() as i32,
+            (body.rect.y / GRID).round() as i32,
+        )
+    };
+    let mut visited = HashSet::from([key(&start)]);
+    let mut queue = VecDeque::from([start]);
+
+    while let Some(node) = queue.pop_front() {
+        if node.rect.check_collision_recs(&zone) {
+            return true;
+        }
+        for direction in [-1.0, 0.0, 1.0] {
+            for jumps in 0..=2 {
+                if direction == 0.0 && jumps == 0 {
+                    continue;
+                }
+                for &hold in HOLD_TIMES {
+                    match simulate(node, direction, jumps, hold, zone, terrain) {
+                        Move::Reached => return true,
+                        Move::Landed(body) => {
+                            if visited.insert(key(&body)) {
+                                queue.push_back(body);
+                            }
+                        }
+                        Move::Lost => (),
+                    }
+                }
+            }
+        }
+    }
+    false
+}
+
+enum Move {
+    Reached,
+    Landed(Body),
+    Lost,
+}
+
+/// Holds `direction` for `hold` seconds, jumping right away if `jumps` is 1 or more and
+/// again at the top of the arc if it is 2, until the body is back on the ground. Walks end
+/// after `hold` or when running into a wall.
+fn simulate(
+    mut body: Body,
+    direction: f32,
+    jumps: u8,
+    hold: f32,
+    zone: Rectangle,
+    terrain: &Terrain,
+) -> Move {
+    let mut jumped = 0;
+    let mut time = 0.0;
+    while time < MAX_MOVE_TIME {
+        let input = PlayerInput {
+            direction: if time < hold { direction } else { 0.0 },
+            jump: jumped < jumps && (jumped == 0 || body.velocity.y >= 0.0),
+            ..Default::default()
+        };
+        if input.jump {
+            jumped += 1;
+        }
+        let alive = advance(&mut body, &input, terrain);
+        time += DT;
+
+        if body.rect.check_collision_recs(&zone) {
+            return Move::Reached;
+        }
+        if !alive {
+            return Move::Lost;
+        }
+        let done = if jumps == 0 {
+            time >= hold || (time > 0.2 && body.velocity.x.abs() < 1.0)
+        } else {
+            jumped == jumps
+        };
+        if done && body.grounded {
+            body.velocity = Vector2::zero();
+            return Move::Landed(body);
+        }
+    }
+    Move::Lost
+}
+
+/// Runs `personality validate [level]`, checking one level or all of them. Returns whether
+/// every checked level is valid.
+pub fn run(level: Option<&str>) -> bool {
+    let source = AssetSource::Directory(ASSETS_DIR.into());
+    let manifest = match source
+        .read(MANIFEST_PATH)
+        .and_then(|bytes| Manifest::parse(&bytes))
+    {
+        Ok(manifest) => manifest,
+        Err(err) => {
+            eprintln!("Failed to read {}: {}", MANIFEST_PATH, err);
+            return false;
+        }
+    };
+
+    let levels = match level {
+        Some(name) => match manifest.level(name) {
+            Some(level) => vec![level],
+            None => {
+                eprintln!("No level named {}", name);
+                return false;
+            }
+        },
+        None => manifest.levels.iter().collect(),
+    };
+
+    let mut valid = true;
+    for level in levels {
+        let problems = validate(&source, &manifest, level);
+        if problems.is_empty() {
+            println!("Level {} is valid", level.name);
+        } else {
+            valid = false;
+            println!("Level {} has {} problem(s):", level.name, problems.len());
+            for problem in problems {
+                println!("  {}", problem);
+            }
+        }
+    }
+    valid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TOWER: Vector2 = Vector2 { x: 6.0, y: 10.0 };
+    const PLAYER: Vector2 = Vector2 { x: 4.0, y: 8.0 };
+
+    /// A 40x30 level walled in on every side but the bottom, with the tower standing on the
+    /// floor to the right of the spawn.
+    fn level(tower_position: [f32; 2], spawn: [f32; 2]) -> LevelInfo {
+        serde_json::from_value(serde_json::json!({
+            "name": "Test",
+            "art": "Art",
+            "metadata": "Metadata",
+            "tower": "Tower",
+            "shield": "Shield",
+            "tower_position": tower_position,
+            "spawn": spawn,
+            "edges": { "left": "wall", "right": "wall", "top": "wall", "bottom": "kill" },
+        }))
+        .unwrap()
+    }
+
+    /// White with a black floor along the bottom five rows.
+    fn metadata(width: i32, height: i32) -> Image {
+        let mut image = Image::gen_image_color(width, height, Color::WHITE);
+        image.draw_rectangle(0, height * 5 / 6, width, height, Color::BLACK);
+        image
+    }
+
+    fn problems(level: &LevelInfo, metadata: Image) -> Vec<String> {
+        let art = Image::gen_image_color(40, 30, Color::WHITE);
+        check(level, &art, metadata, TOWER, PLAYER)
+    }
+
+    #[test]
+    fn valid_level() {
+        assert_eq!(
+            problems(&level([30.0, 15.0], [5.0, 10.0]), metadata(40, 30)),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn size_mismatch() {
+        // Scaled up, the floor starts a row higher, so the tower is raised to match.
+        let problems = problems(&level([30.0, 14.0], [5.0, 10.0]), metadata(20, 15));
+        assert_eq!(
+            problems,
+            ["metadata Metadata is 20x15 but the art Art is 40x30"]
+        );
+    }
+
+    #[test]
+    fn spawn_inside_solid() {
+        let problems = problems(&level([30.0, 15.0], [5.0, 22.0]), metadata(40, 30));
+        assert_eq!(problems, ["the spawn is inside the terrain"]);
+    }
+
+    #[test]
+    fn tower_overlap() {
+        let problems = problems(&level([30.0, 20.0], [5.0, 10.0]), metadata(40, 30));
+        assert_eq!(problems, ["tower at [30.0, 20.0] overlaps the terrain"]);
+    }
+
+    #[test]
+    fn unreachable_tower() {
+        let mut metadata = metadata(40, 30);
+        // A wall from the ceiling to the floor between the spawn and the tower.
+        metadata.draw_rectangle(16, 0, 4, 30, Color::BLACK);
+        let problems = problems(&level([30.0, 15.0], [5.0, 10.0]), metadata);
+        assert_eq!(problems, ["the tower can't be reached from the spawn"]);
+    }
+}
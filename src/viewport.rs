@@ -0,0 +1,158 @@
+use serde::{Deserialize, Serialize};
+
+use crate::resources::*;
+
+/// Window size used when leaving fullscreen.
+const WINDOW_SIZE: (i32, i32) = (860, 480);
+
+/// How the native resolution image is stretched to the window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scaling {
+    /// Whole multiples only, so every art pixel covers the same number of screen pixels.
+    #[default]
+    Integer,
+    /// Fills as much of the window as possible, keeping the aspect ratio.
+    Letterbox,
+}
+
+/// The world is drawn into a render texture at the scene's native resolution, which is
+/// then scaled up to the window. Converts between window and native coordinates.
+pub struct Viewport {
+    target: Option<RenderTexture2D>,
+    scaling: Scaling,
+    size: (i32, i32),
+    scale: f32,
+    origin: Vector2,
+}
+
+impl Viewport {
+    pub fn new(scaling: Scaling) -> Self {
+        Self {
+            target: None,
+            scaling,
+            size: (0, 0),
+            scale: 1.0,
+            origin: Vector2::zero(),
+        }
+    }
+
+    pub fn scaling(&self) -> Scaling {
+        self.scaling
+    }
+
+    /// Fits the native resolution to the window and the `scene` size, recreating the
+    /// render texture when it changes. The native height is always the scene height; the
+    /// width follows the window's aspect ratio up to the scene width.
+    pub fn update(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, scene: Vector2) {
+        let screen = rvec2(rl.get_screen_width(), rl.get_screen_height());
+        let fit = (screen.y / scene.y).max(1.0);
+        let fit = match self.scaling {
+            Scaling::Integer => fit.floor(),
+            Scaling::Letterbox => fit,
+        };
+        let size = (
+            ((screen.x / fit).ceil() as i32).clamp(1, scene.x as i32),
+            scene.y as i32,
+        );
+
+        self.scale = match self.scaling {
+            Scaling::Integer => fit,
+            Scaling::Letterbox => (screen.x / size.0 as f32).min(screen.y / size.1 as f32),
+        };
+        let margin = (screen - rvec2(size.0, size.1) * self.scale) / 2.0;
+        self.origin = rvec2(margin.x.max(0.0).floor(), margin.y.max(0.0).floor());
+
+        if self.target.is_none() || self.size != size {
+            self.size = size;
+            match rl.load_render_texture(thread, size.0 as u32, size.1 as u32) {
+                Ok(target) => self.target = Some(target),
+                Err(err) => eprintln!("Failed to create the render target: {}", err),
+            }
+        }
+    }
+
+    /// `F2` switches between integer and letterboxed scaling, `F11` toggles fullscreen.
+    /// Returns whether anything changed, so the caller can persist the settings.
+    pub fn controls(&mut self, rl: &mut RaylibHandle) -> bool {
+        let mut changed = false;
+        if rl.is_key_pressed(KeyboardKey::KEY_F2) {
+            self.scaling = match self.scaling {
+                Scaling::Integer => Scaling::Letterbox,
+                Scaling::Letterbox => Scaling::Integer,
+            };
+            changed = true;
+        }
+        if rl.is_key_pressed(KeyboardKey::KEY_F11) {
+            set_fullscreen(rl, !rl.is_window_fullscreen());
+            changed = true;
+        }
+        changed
+    }
+
+    /// Native resolution of the world image.
+    pub fn size(&self) -> Vector2 {
+        rvec2(self.size.0, self.size.1)
+    }
+
+    /// Window pixels per native pixel.
+    pub fn scale(&self) -> f32 {
+        self.scale
+    }
+
+    /// The part of the window the world image covers.
+    pub fn rect(&self) -> Rectangle {
+        let size = self.size() * self.scale;
+        rrect(self.origin.x, self.origin.y, size.x, size.y)
+    }
+
+    pub fn screen_to_native(&self, position: Vector2) -> Vector2 {
+        (position - self.origin) / self.scale
+    }
+
+    pub fn native_to_screen(&self, position: Vector2) -> Vector2 {
+        position * self.scale + self.origin
+    }
+
+    pub fn screen_to_world(&self, position: Vector2, camera: Camera2D) -> Vector2 {
+        (self.screen_to_native(position) - camera.offset) / camera.zoom + camera.target
+    }
+
+    pub fn world_to_screen(&self, position: Vector2, camera: Camera2D) -> Vector2 {
+        self.native_to_screen((position - camera.target) * camera.zoom + camera.offset)
+    }
+
+    /// The render texture to draw the world into, if it could be created.
+    pub fn target(&mut self) -> Option<&mut RenderTexture2D> {
+        self.target.as_mut()
+    }
+
+    /// Blits the world image to the window.
+    pub fn draw(&self, d: &mut RaylibDrawHandle) {
+        if let Some(target) = &self.target {
+            let size = self.size();
+            d.draw_texture_pro(
+                target.texture(),
+                rrect(0, 0, size.x, -size.y),
+                self.rect(),
+                Vector2::zero(),
+                0.0,
+                Color::WHITE,
+            );
+        }
+    }
+}
+
+/// Fullscreen at the monitor's resolution, or back to the default window size.
+pub fn set_fullscreen(rl: &mut RaylibHandle, fullscreen: bool) {
+    if rl.is_window_fullscreen() == fullscreen {
+        return;
+    }
+    if fullscreen {
+        let monitor = get_current_monitor();
+        rl.set_window_size(get_monitor_width(monitor), get_monitor_height(monitor));
+        rl.toggle_fullscreen();
+    } else {
+        rl.toggle_fullscreen();
+        rl.set_window_size(WINDOW_SIZE.0, WINDOW_SIZE.1);
+    }
+}